@@ -0,0 +1,165 @@
+use {Point, RoadGraph, NodeId, EdgeId};
+use graph::{self, Exit};
+
+// nodes closer than this are treated as the same junction
+const WELD_DISTANCE: f64 = 0.01;
+
+// smallest area considered enclosed, anything less is a sliver between overlapping roads
+const MIN_AREA: f64 = 1e-6;
+
+// an area enclosed by roads
+#[derive(Debug, Clone)]
+pub struct Block {
+    polygon: Vec<Point>,
+    area: f64,
+    roads: Vec<EdgeId>,
+    sides: Vec<EdgeId>,
+}
+
+impl Block {
+    // not repeating the first point at the end
+    pub fn polygon(&self) -> &[Point] {
+        &self.polygon
+    }
+
+    pub fn area(&self) -> f64 {
+        self.area
+    }
+
+    // in order around the polygon
+    pub fn roads(&self) -> &[EdgeId] {
+        &self.roads
+    }
+
+    // the road along each side, from each point of the polygon to the next
+    pub fn sides(&self) -> &[EdgeId] {
+        &self.sides
+    }
+
+    // mean of the polygon's points
+    pub fn centre(&self) -> Point {
+        let n = self.polygon.len() as f64;
+        let (x, y) = self.polygon
+            .iter()
+            .fold((0.0, 0.0), |(x, y), p| (x + p.x(), y + p.y()));
+        Point::new(x / n, y / n)
+    }
+}
+
+// the faces of the planar road graph, minus the unbounded outside of each connected area
+pub fn extract(graph: &RoadGraph) -> Vec<Block> {
+    let welded = graph.weld_nodes(WELD_DISTANCE);
+
+    let mut neighbours: Vec<Vec<(EdgeId, NodeId, Point)>> = vec![Vec::new(); graph.nodes().len()];
+    for (i, edge) in graph.edges().iter().enumerate() {
+        let (a, b) = (welded[edge.from().0], welded[edge.to().0]);
+        if a == b || neighbours[a.0].iter().any(|&(_, to, _)| to == b) {
+            // collapsed or parallel
+            continue;
+        }
+
+        neighbours[a.0].push((EdgeId(i), b, graph.node(b).position()));
+        neighbours[b.0].push((EdgeId(i), a, graph.node(a).position()));
+    }
+
+    let exits: Vec<Vec<Exit>> = neighbours
+        .into_iter()
+        .enumerate()
+        .map(|(node, roads)| graph::sorted_exits(&graph.nodes()[node].position(), roads.into_iter()))
+        .collect();
+
+    let mut visited: Vec<Vec<bool>> = exits.iter().map(|e| vec![false; e.len()]).collect();
+    let mut blocks = Vec::new();
+
+    for start in 0..exits.len() {
+        for start_exit in 0..exits[start].len() {
+            if visited[start][start_exit] {
+                continue;
+            }
+
+            // walk around the face, always taking the next exit clockwise from where we came in
+            let mut face: Vec<(usize, EdgeId)> = Vec::new();
+            let (mut node, mut exit) = (start, start_exit);
+            while !visited[node][exit] {
+                visited[node][exit] = true;
+
+                let Exit { to, edge, .. } = exits[node][exit];
+                face.push((node, edge));
+
+                let back = exits[to.0].iter().position(|e| e.to == NodeId(node)).unwrap();
+                let count = exits[to.0].len();
+                node = to.0;
+                exit = (back + count - 1) % count;
+            }
+
+            if let Some(block) = to_block(graph, remove_spikes(face)) {
+                blocks.push(block);
+            }
+        }
+    }
+
+    blocks
+}
+
+// removes dead ends poking into the face, which are walked up and back down again
+fn remove_spikes(face: Vec<(usize, EdgeId)>) -> Vec<(usize, EdgeId)> {
+    let mut cleaned: Vec<(usize, EdgeId)> = Vec::with_capacity(face.len());
+    for step in face {
+        if cleaned.last().is_some_and(|&(_, edge)| edge == step.1) {
+            cleaned.pop();
+        } else {
+            cleaned.push(step);
+        }
+    }
+
+    // and across the start
+    while cleaned.len() >= 2 && cleaned.first().unwrap().1 == cleaned.last().unwrap().1 {
+        cleaned.pop();
+        cleaned.remove(0);
+    }
+
+    cleaned
+}
+
+fn to_block(graph: &RoadGraph, face: Vec<(usize, EdgeId)>) -> Option<Block> {
+    if face.len() < 3 {
+        return None;
+    }
+
+    let polygon: Vec<Point> = face.iter()
+        .map(|&(node, _)| graph.nodes()[node].position())
+        .collect();
+
+    // the outside of an area winds the other way, so is negative
+    let area = signed_area(&polygon);
+    if area < MIN_AREA {
+        return None;
+    }
+
+    let sides: Vec<EdgeId> = face.iter().map(|&(_, edge)| edge).collect();
+    let mut roads: Vec<EdgeId> = Vec::with_capacity(sides.len());
+    for &edge in &sides {
+        if !roads.contains(&edge) {
+            roads.push(edge);
+        }
+    }
+
+    Some(Block {
+             polygon,
+             area,
+             roads,
+             sides,
+         })
+}
+
+// shoelace formula
+pub fn signed_area(polygon: &[Point]) -> f64 {
+    let n = polygon.len();
+    let twice: f64 = (0..n)
+        .map(|i| {
+                 let (a, b) = (&polygon[i], &polygon[(i + 1) % n]);
+                 a.x() * b.y() - b.x() * a.y()
+             })
+        .sum();
+    twice / 2.0
+}
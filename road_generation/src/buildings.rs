@@ -0,0 +1,228 @@
+use {Point, Road, EdgeId};
+use config::BuildingConfig;
+use lots::{self, Lot};
+use geometry;
+use rand::{Rng, XorShiftRng};
+
+// footprints with a smaller width or depth than this are not built
+const MIN_SIZE: f64 = 2.0;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BuildingShape {
+    Rectangle,
+    // missing a rear corner
+    L,
+    // missing the middle of the rear
+    U,
+}
+
+#[derive(Debug, Clone)]
+pub struct Building {
+    footprint: Vec<Point>,
+    shape: BuildingShape,
+    storeys: u32,
+    height: f64,
+    road: EdgeId,
+}
+
+impl Building {
+    // anticlockwise, not repeating the first point at the end
+    pub fn footprint(&self) -> &[Point] {
+        &self.footprint
+    }
+
+    pub fn shape(&self) -> BuildingShape {
+        self.shape
+    }
+
+    pub fn storeys(&self) -> u32 {
+        self.storeys
+    }
+
+    pub fn height(&self) -> f64 {
+        self.height
+    }
+
+    // the road the lot faces
+    pub fn road(&self) -> EdgeId {
+        self.road
+    }
+}
+
+// a building set back from the street and inset from the neighbours, facing the lot's road.
+// storeys is an inclusive range, roads are those the lot's sides refer to
+pub fn generate(lot: &Lot,
+                roads: &[Road],
+                config: &BuildingConfig,
+                storeys: [u32; 2],
+                rng: &mut XorShiftRng)
+                -> Option<Building> {
+    let (a, b) = lot.frontage();
+    let length = geometry::distance(&a, &b);
+    if length <= 0.0 {
+        return None;
+    }
+
+    // along the street, and away from it into the lot
+    let u = [(b.x() - a.x()) / length, (b.y() - a.y()) / length];
+    let v = [-u[1], u[0]];
+
+    let buildable = inset(lot, roads, config);
+    if buildable.len() < 3 {
+        return None;
+    }
+
+    let (u0, u1, v0, v1) = fit_rectangle(&buildable, &u, &v)?;
+    let (width, depth) = (u1 - u0, v1 - v0);
+    if width < MIN_SIZE || depth < MIN_SIZE {
+        return None;
+    }
+
+    // can't cut into buildings too small to have wings
+    let roll: f64 = rng.gen();
+    let shape = if width < MIN_SIZE * 3.0 || depth < MIN_SIZE * 2.0 {
+        BuildingShape::Rectangle
+    } else if roll < config.l_shape_chance {
+        BuildingShape::L
+    } else if roll < config.l_shape_chance + config.u_shape_chance {
+        BuildingShape::U
+    } else {
+        BuildingShape::Rectangle
+    };
+
+    let vm = v0 + depth / 2.0;
+    let corners: Vec<(f64, f64)> = match shape {
+        BuildingShape::Rectangle => vec![(u0, v0), (u1, v0), (u1, v1), (u0, v1)],
+        BuildingShape::L => {
+            let um = u0 + width / 2.0;
+            vec![(u0, v0), (u1, v0), (u1, vm), (um, vm), (um, v1), (u0, v1)]
+        }
+        BuildingShape::U => {
+            let (ua, ub) = (u0 + width / 3.0, u1 - width / 3.0);
+            vec![(u0, v0), (u1, v0), (u1, v1), (ub, v1), (ub, vm), (ua, vm), (ua, v1), (u0, v1)]
+        }
+    };
+
+    let storeys = rng.gen_range(storeys[0], storeys[1].max(storeys[0]) + 1);
+    Some(Building {
+             footprint: corners
+                 .into_iter()
+                 .map(|(cu, cv)| Point::new(u[0] * cu + v[0] * cv, u[1] * cu + v[1] * cv))
+                 .collect(),
+             shape,
+             storeys,
+             height: storeys as f64 * config.storey_height,
+             road: lot.road(),
+         })
+}
+
+// the lot shrunk by the setback from the edge of roads and the inset from everything else.
+// the lot's sides run along the middle of its roads
+fn inset(lot: &Lot, roads: &[Road], config: &BuildingConfig) -> Vec<Point> {
+    let points = lot.polygon();
+    let n = points.len();
+    let mut polygon: Vec<lots::Side> = points.iter().map(|&p| (p, None)).collect();
+
+    for (i, road) in lot.sides().iter().enumerate() {
+        let (a, b) = (points[i], points[(i + 1) % n]);
+        let length = geometry::distance(&a, &b);
+        if length <= 0.0 {
+            continue;
+        }
+
+        // inwards, as the polygon is anticlockwise
+        let normal = [-(b.y() - a.y()) / length, (b.x() - a.x()) / length];
+        let margin = match *road {
+            Some(edge) => roads[edge.0].width() / 2.0 + config.setback,
+            None => config.inset,
+        };
+
+        polygon = lots::clip_half(&polygon, |p| {
+            (p.x() - a.x()) * normal[0] + (p.y() - a.y()) * normal[1] - margin
+        });
+    }
+
+    polygon.into_iter().map(|(p, _)| p).collect()
+}
+
+// the largest rectangle aligned to the given axes around the middle of the polygon, as
+// (min u, max u, min v, max v)
+fn fit_rectangle(polygon: &[Point], u: &[f64; 2], v: &[f64; 2]) -> Option<(f64, f64, f64, f64)> {
+    let project = |p: &Point, axis: &[f64; 2]| p.x() * axis[0] + p.y() * axis[1];
+    let us: Vec<f64> = polygon.iter().map(|p| project(p, u)).collect();
+    let vs: Vec<f64> = polygon.iter().map(|p| project(p, v)).collect();
+
+    let min = |xs: &[f64]| xs.iter().cloned().fold(f64::MAX, f64::min);
+    let max = |xs: &[f64]| xs.iter().cloned().fold(f64::MIN, f64::max);
+    let (cu, cv) = ((min(&us) + max(&us)) / 2.0, (min(&vs) + max(&vs)) / 2.0);
+    let (hu, hv) = ((max(&us) - min(&us)) / 2.0, (max(&vs) - min(&vs)) / 2.0);
+
+    let fits = |scale: f64| {
+        [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)]
+            .iter()
+            .all(|&(su, sv)| {
+                     let (pu, pv) = (cu + su * hu * scale, cv + sv * hv * scale);
+                     let p = Point::new(u[0] * pu + v[0] * pv, u[1] * pu + v[1] * pv);
+                     geometry::point_in_polygon(&p, polygon)
+                 })
+    };
+
+    // binary search for the largest scale that fits
+    let (mut low, mut high) = (0.0, 1.0);
+    if fits(high) {
+        low = high;
+    } else {
+        for _ in 0..12 {
+            let mid = (low + high) / 2.0;
+            if fits(mid) {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+    }
+
+    if low <= 0.0 {
+        None
+    } else {
+        Some((cu - hu * low, cu + hu * low, cv - hv * low, cv + hv * low))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use config::Config;
+    use geometry;
+    use toml;
+    use RoadMap;
+
+    #[test]
+    fn buildings_stay_off_the_road() {
+        let mut config: Config = toml::from_str(include_str!("../../config.toml")).unwrap();
+        config.resolve_classes().unwrap();
+
+        let mut roadmap = RoadMap::with_seed(config, 1).unwrap();
+        while !roadmap.is_finished() {
+            roadmap.advance().unwrap();
+        }
+
+        let buildings = roadmap.buildings();
+        assert!(!buildings.is_empty());
+
+        // no side of any footprint comes within half a road's width of its middle
+        for building in &buildings {
+            let footprint = building.footprint();
+            let n = footprint.len();
+            for road in roadmap.roads() {
+                let (from, to) = (road.from().unwrap(), road.to().unwrap());
+                for i in 0..n {
+                    let distance = geometry::segment_distance(&footprint[i],
+                                                              &footprint[(i + 1) % n],
+                                                              &from,
+                                                              &to);
+                    assert!(distance >= road.width() / 2.0 - 1e-6, "{:?} on {:?}", building, road);
+                }
+            }
+        }
+    }
+}
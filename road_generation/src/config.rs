@@ -1,32 +1,81 @@
-use RoadType;
+use {RoadType, Point, RoadProfile, Surface};
+use rules::GenerationRule;
+use geometry;
 use std::io::prelude::*;
 use std::fs::File;
 use std::error::Error;
 use std::io;
+use std::collections::HashMap;
 use toml;
 
-#[derive(Clone, Deserialize, Default)]
+#[derive(Clone, Serialize, Deserialize, Default)]
 pub struct Config {
     pub window: WindowConfig,
-    generation: GenerationConfigs,
+
+    // most important first, each branching into its child
+    classes: Vec<RoadClass>,
+
+    // drives all random decisions, chosen randomly if not specified
+    pub seed: Option<u64>,
+
+    pub density: Option<DensityConfig>,
+    pub obstacles: Option<ObstacleConfig>,
+    pub terrain: Option<TerrainConfig>,
+
+    // areas using different rules, grid everywhere if not specified
+    #[serde(default)]
+    pub zones: Vec<ZoneConfig>,
+
+    #[serde(default)]
+    pub lots: LotConfig,
+
+    #[serde(default)]
+    pub buildings: BuildingConfig,
+
+    // a single random centre if not specified
+    pub frontier: Option<FrontierConfig>,
+
+    // no highways between centres if not specified
+    pub highways: Option<HighwayConfig>,
+
+    // the roads are left as grown if not specified
+    pub cleanup: Option<CleanupConfig>,
+
+    #[serde(default)]
+    pub export: ExportConfig,
 }
 
-#[derive(Clone, Deserialize, Default)]
+#[derive(Clone, Serialize, Deserialize, Default)]
 pub struct WindowConfig {
     pub width: u32,
     pub height: u32,
     pub growth_increment: Option<u32>,
 }
 
-#[derive(Clone, Deserialize, Default)]
-struct GenerationConfigs {
-    large: GenerationConfig,
-    medium: Option<GenerationConfig>,
-    small: Option<GenerationConfig>,
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RoadClass {
+    pub name: String,
+
+    // the class branching off this one, which must come later. none for the last
+    pub child: Option<String>,
+
+    // rgb when drawn
+    #[serde(default)]
+    pub colour: [u8; 3],
+
+    // openstreetmap highway tag, primary, secondary then residential by importance if
+    // not specified
+    pub highway: Option<String>,
+
+    pub generation: GenerationConfig,
+
+    #[serde(skip)]
+    child_type: Option<RoadType>,
 }
 
 
-#[derive(Clone, Deserialize, Default)]
+// any field added here also needs adding to GenerationOverride, so zones can override it
+#[derive(Clone, Serialize, Deserialize, Default)]
 pub struct GenerationConfig {
     pub merge_range: f64,
     pub fuel_range: [u32; 2],
@@ -34,6 +83,455 @@ pub struct GenerationConfig {
     pub road_length: f64,
     pub organic_angle: f64,
     pub child_chance: f64,
+
+    // time before proposals of this type are evaluated, lower grows first
+    #[serde(default)]
+    pub delay: u32,
+
+    // max degrees a proposal may be turned towards denser areas
+    #[serde(default)]
+    pub density_angle: f64,
+
+    // longest obstacle crossing, roads are cut short at obstacles if not specified
+    pub max_bridge_length: Option<f64>,
+
+    // steepest rise over run allowed, any if not specified
+    pub max_slope: Option<f64>,
+
+    // sharpest degrees allowed between roads meeting at a junction
+    #[serde(default)]
+    pub min_junction_angle: f64,
+
+    // shortest road allowed after merging
+    #[serde(default)]
+    pub min_length: f64,
+
+    // how far a road may be stretched to meet a road just ahead of it
+    #[serde(default)]
+    pub extend_range: f64,
+
+    // roads keep at least half their combined widths apart
+    #[serde(default)]
+    pub width: f64,
+    #[serde(default = "GenerationConfig::default_lanes")]
+    pub lanes: u32,
+    // km/h
+    #[serde(default = "GenerationConfig::default_speed_limit")]
+    pub speed_limit: f64,
+    #[serde(default)]
+    pub surface: Surface,
+
+    // chance of a lot facing this type having a building
+    #[serde(default = "GenerationConfig::default_building_chance")]
+    pub building_chance: f64,
+
+    // inclusive range for buildings facing this type
+    #[serde(default = "GenerationConfig::default_storeys")]
+    pub storeys: [u32; 2],
+}
+
+impl GenerationConfig {
+    fn default_building_chance() -> f64 {
+        1.0
+    }
+
+    fn default_storeys() -> [u32; 2] {
+        [1, 2]
+    }
+
+    fn default_lanes() -> u32 {
+        RoadProfile::default().lanes
+    }
+
+    fn default_speed_limit() -> f64 {
+        RoadProfile::default().speed_limit
+    }
+
+    pub fn profile(&self) -> RoadProfile {
+        RoadProfile {
+            width: self.width,
+            lanes: self.lanes,
+            speed_limit: self.speed_limit,
+            surface: self.surface,
+        }
+    }
+}
+
+// any of a class's generation config, the rest is kept from the class
+#[derive(Clone, Serialize, Deserialize, Default)]
+pub struct GenerationOverride {
+    pub merge_range: Option<f64>,
+    pub fuel_range: Option<[u32; 2]>,
+    pub road_chance: Option<f64>,
+    pub road_length: Option<f64>,
+    pub organic_angle: Option<f64>,
+    pub child_chance: Option<f64>,
+    pub delay: Option<u32>,
+    pub density_angle: Option<f64>,
+    pub max_bridge_length: Option<f64>,
+    pub max_slope: Option<f64>,
+    pub min_junction_angle: Option<f64>,
+    pub min_length: Option<f64>,
+    pub extend_range: Option<f64>,
+    pub width: Option<f64>,
+    pub lanes: Option<u32>,
+    pub speed_limit: Option<f64>,
+    pub surface: Option<Surface>,
+    pub building_chance: Option<f64>,
+    pub storeys: Option<[u32; 2]>,
+}
+
+impl GenerationOverride {
+    fn apply(&self, base: &GenerationConfig) -> GenerationConfig {
+        GenerationConfig {
+            merge_range: self.merge_range.unwrap_or(base.merge_range),
+            fuel_range: self.fuel_range.unwrap_or(base.fuel_range),
+            road_chance: self.road_chance.unwrap_or(base.road_chance),
+            road_length: self.road_length.unwrap_or(base.road_length),
+            organic_angle: self.organic_angle.unwrap_or(base.organic_angle),
+            child_chance: self.child_chance.unwrap_or(base.child_chance),
+            delay: self.delay.unwrap_or(base.delay),
+            density_angle: self.density_angle.unwrap_or(base.density_angle),
+            max_bridge_length: self.max_bridge_length.or(base.max_bridge_length),
+            max_slope: self.max_slope.or(base.max_slope),
+            min_junction_angle: self.min_junction_angle.unwrap_or(base.min_junction_angle),
+            min_length: self.min_length.unwrap_or(base.min_length),
+            extend_range: self.extend_range.unwrap_or(base.extend_range),
+            width: self.width.unwrap_or(base.width),
+            lanes: self.lanes.unwrap_or(base.lanes),
+            speed_limit: self.speed_limit.unwrap_or(base.speed_limit),
+            surface: self.surface.unwrap_or(base.surface),
+            building_chance: self.building_chance.unwrap_or(base.building_chance),
+            storeys: self.storeys.unwrap_or(base.storeys),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "source", rename_all = "lowercase")]
+pub enum FieldConfig {
+    Constant { value: f64 },
+    Noise { scale: f64, octaves: u32 },
+    // greyscale pgm stretched over the window
+    Image { path: String },
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DensityConfig {
+    // can instead be provided with RoadMap::set_density
+    pub field: Option<FieldConfig>,
+
+    // no branches are proposed where density is below this
+    #[serde(default)]
+    pub branch_threshold: f64,
+
+    // road length multiplier at density 0 and 1
+    #[serde(default = "DensityConfig::default_length_scale")]
+    pub length_scale: [f64; 2],
+}
+
+#[derive(Clone, Serialize, Deserialize, Default)]
+pub struct ObstacleConfig {
+    // world units
+    #[serde(default)]
+    pub polygons: Vec<Vec<[f64; 2]>>,
+
+    // obstacles wherever the mask is below the threshold
+    pub mask: Option<FieldConfig>,
+    #[serde(default = "ObstacleConfig::default_mask_threshold")]
+    pub mask_threshold: f64,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TerrainConfig {
+    pub field: FieldConfig,
+
+    // elevation of a field value of 1
+    pub height_scale: f64,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ZoneConfig {
+    pub rule: GenerationRule,
+
+    // matches anywhere not covered by another zone if not specified
+    pub shape: Option<ZoneShape>,
+
+    // for rules that grow around a point, defaults to the middle of the shape
+    pub centre: Option<[f64; 2]>,
+
+    // overrides fields of the global config for each road class named
+    #[serde(default)]
+    pub generation: HashMap<String, GenerationOverride>,
+
+    // the class configs with the overrides applied, filled in when the classes are resolved
+    #[serde(skip)]
+    resolved: HashMap<String, GenerationConfig>,
+
+    // points of a polygon shape, converted once when the classes are resolved
+    #[serde(skip)]
+    polygon: Vec<Point>,
+
+    // replaces the global lot config if given
+    pub lots: Option<LotConfig>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum ZoneShape {
+    Rect {
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+    },
+    Circle { centre: [f64; 2], radius: f64 },
+    Polygon { points: Vec<[f64; 2]> },
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct FrontierConfig {
+    // explicit roads to grow from
+    #[serde(default)]
+    pub roads: Vec<SeedRoadConfig>,
+
+    // random town centres to grow from, in addition to any roads
+    #[serde(default)]
+    pub centres: u32,
+    #[serde(default)]
+    pub min_spacing: f64,
+    // degrees, random for each centre if not specified
+    pub heading: Option<f64>,
+    // the first if not specified
+    pub class: Option<String>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SeedRoadConfig {
+    pub position: [f64; 2],
+    // degrees
+    pub heading: f64,
+    pub class: String,
+}
+
+impl FrontierConfig {
+    // the original single random centre
+    pub fn single_centre() -> FrontierConfig {
+        FrontierConfig {
+            roads: Vec::new(),
+            centres: 1,
+            min_spacing: 0.0,
+            heading: Some(0.0),
+            class: None,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct HighwayConfig {
+    // links beyond the minimum spanning tree between centres, shortest first
+    #[serde(default)]
+    pub extra_links: u32,
+    // size of the grid routed through around obstacles
+    #[serde(default = "HighwayConfig::default_cell_size")]
+    pub cell_size: f64,
+    // the first if not specified
+    pub class: Option<String>,
+}
+
+impl HighwayConfig {
+    fn default_cell_size() -> f64 {
+        10.0
+    }
+}
+
+// each step is skipped when zero
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct CleanupConfig {
+    // dead ends made of fewer roads than this back to a junction are removed
+    #[serde(default)]
+    pub min_dead_end: u32,
+    // dead ends are joined onto the nearest road up to this far from them
+    #[serde(default)]
+    pub extend_range: f64,
+    // connected areas of fewer roads than this are removed
+    #[serde(default)]
+    pub min_component: u32,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct ExportConfig {
+    // exported in world units if not specified
+    pub projection: Option<ProjectionConfig>,
+
+    #[serde(default)]
+    pub svg: SvgConfig,
+}
+
+// places the map on the globe
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct ProjectionConfig {
+    // latitude and longitude of the middle of the map, degrees
+    pub origin: [f64; 2],
+    // metres per world unit
+    #[serde(default = "ProjectionConfig::default_scale")]
+    pub scale: f64,
+}
+
+impl ProjectionConfig {
+    fn default_scale() -> f64 {
+        1.0
+    }
+}
+
+impl Default for ProjectionConfig {
+    // null island, a metre per world unit
+    fn default() -> ProjectionConfig {
+        ProjectionConfig {
+            origin: [0.0, 0.0],
+            scale: ProjectionConfig::default_scale(),
+        }
+    }
+}
+
+// drawn along with the roads, each off by default
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+pub struct SvgConfig {
+    #[serde(default)]
+    pub vertices: bool,
+    #[serde(default)]
+    pub blocks: bool,
+    #[serde(default)]
+    pub zones: bool,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct LotConfig {
+    // smaller areas left over from splitting are discarded
+    pub min_area: f64,
+    // larger areas are split
+    pub max_area: f64,
+    // shortest length along a road for a lot to be kept
+    pub min_frontage: f64,
+}
+
+impl Default for LotConfig {
+    fn default() -> LotConfig {
+        LotConfig {
+            min_area: 30.0,
+            max_area: 250.0,
+            min_frontage: 4.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct BuildingConfig {
+    // distance from the edge of roads
+    pub setback: f64,
+    // distance from neighbouring lots
+    pub inset: f64,
+
+    pub l_shape_chance: f64,
+    pub u_shape_chance: f64,
+
+    pub storey_height: f64,
+}
+
+impl Default for BuildingConfig {
+    fn default() -> BuildingConfig {
+        BuildingConfig {
+            setback: 1.5,
+            inset: 0.75,
+            l_shape_chance: 0.2,
+            u_shape_chance: 0.1,
+            storey_height: 3.0,
+        }
+    }
+}
+
+impl ZoneConfig {
+    // applies the overrides to the classes they name
+    fn resolve(&mut self, classes: &[RoadClass]) -> Result<(), String> {
+        let mut resolved = HashMap::new();
+        for (name, generation) in &self.generation {
+            let class = match classes.iter().find(|c| &c.name == name) {
+                Some(class) => class,
+                None => return Err(format!("Unknown road class '{}' in zone", name)),
+            };
+            resolved.insert(name.clone(), generation.apply(&class.generation));
+        }
+        self.resolved = resolved;
+
+        self.polygon = match self.shape {
+            Some(ZoneShape::Polygon { ref points }) => {
+                points.iter().map(|&p| Point::from(p)).collect()
+            }
+            _ => Vec::new(),
+        };
+
+        Ok(())
+    }
+
+    fn contains(&self, point: &Point) -> bool {
+        match self.shape {
+            None => true,
+            Some(ZoneShape::Rect { x, y, width, height }) => {
+                point.x() >= x && point.x() < x + width && point.y() >= y &&
+                point.y() < y + height
+            }
+            Some(ZoneShape::Circle { centre, radius }) => {
+                (point.x() - centre[0]).hypot(point.y() - centre[1]) <= radius
+            }
+            Some(ZoneShape::Polygon { .. }) => geometry::point_in_polygon(point, &self.polygon),
+        }
+    }
+
+    // centre of the shape if not specified, None for shapeless zones
+    pub fn centre(&self) -> Option<[f64; 2]> {
+        if self.centre.is_some() {
+            return self.centre;
+        }
+
+        match self.shape {
+            None => None,
+            Some(ZoneShape::Rect { x, y, width, height }) => {
+                Some([x + width / 2.0, y + height / 2.0])
+            }
+            Some(ZoneShape::Circle { centre, .. }) => Some(centre),
+            Some(ZoneShape::Polygon { ref points }) => {
+                let n = points.len().max(1) as f64;
+                let sum = points.iter().fold([0.0; 2], |acc, p| [acc[0] + p[0], acc[1] + p[1]]);
+                Some([sum[0] / n, sum[1] / n])
+            }
+        }
+    }
+
+    fn generation(&self, class: &str) -> Option<&GenerationConfig> {
+        self.resolved.get(class)
+    }
+}
+
+impl ObstacleConfig {
+    fn default_mask_threshold() -> f64 {
+        0.5
+    }
+}
+
+impl DensityConfig {
+    fn default_length_scale() -> [f64; 2] {
+        [1.0, 1.0]
+    }
+}
+
+impl Default for DensityConfig {
+    fn default() -> DensityConfig {
+        DensityConfig {
+            field: None,
+            branch_threshold: 0.0,
+            length_scale: DensityConfig::default_length_scale(),
+        }
+    }
 }
 
 impl Config {
@@ -43,7 +541,7 @@ impl Config {
         let mut contents = String::new();
         file.read_to_string(&mut contents)?;
 
-        let config: Config = match toml::from_str(&contents) {
+        let mut config: Config = match toml::from_str(&contents) {
             Ok(c) => c,
             Err(e) => {
                 return Err(io::Error::new(io::ErrorKind::Other,
@@ -52,10 +550,50 @@ impl Config {
             }
         };
 
+        config
+            .resolve_classes()
+            .map_err(io::Error::other)?;
         Ok(config)
     }
 
-    // TODO validate
+    // looks up each class's child, checking the hierarchy can only go downwards
+    pub(crate) fn resolve_classes(&mut self) -> Result<(), String> {
+        if self.classes.is_empty() {
+            return Err(String::from("No road classes"));
+        }
+
+        for i in 0..self.classes.len() {
+            let name = &self.classes[i].name;
+            if self.classes[..i].iter().any(|c| &c.name == name) {
+                return Err(format!("Duplicate road class '{}'", name));
+            }
+        }
+
+        for i in 0..self.classes.len() {
+            let child = match self.classes[i].child {
+                Some(ref child) => child.clone(),
+                None => continue,
+            };
+
+            let child_type = match self.road_type(&child) {
+                Some(t) if t.0 > i => t,
+                Some(_) => {
+                    return Err(format!("Road class '{}' must come before its child '{}'",
+                                       self.classes[i].name,
+                                       child))
+                }
+                None => return Err(format!("Unknown road class '{}'", child)),
+            };
+            self.classes[i].child_type = Some(child_type);
+        }
+
+        for zone in &mut self.zones {
+            zone.resolve(&self.classes)?;
+        }
+
+        Ok(())
+    }
+
     pub fn load(self, path: &'static str) -> (bool, Config) {
         match Config::load_unsafe(path) {
             Ok(config) => (true, config),
@@ -66,14 +604,135 @@ impl Config {
         }
     }
 
-    // defaults to Large config if not specified in config
+    pub fn classes(&self) -> &[RoadClass] {
+        &self.classes
+    }
+
+    pub fn class(&self, road_type: RoadType) -> &RoadClass {
+        &self.classes[road_type.0]
+    }
+
+    pub fn road_type(&self, name: &str) -> Option<RoadType> {
+        self.classes.iter().position(|c| c.name == name).map(RoadType)
+    }
+
+    // the class branching off roads of the given type, if any
+    pub fn child(&self, road_type: RoadType) -> Option<RoadType> {
+        self.class(road_type).child_type
+    }
+
     pub fn generation(&self, road_type: &RoadType) -> &GenerationConfig {
-        let gen = match *road_type {
-            RoadType::Large => Some(&self.generation.large),
-            RoadType::Medium => self.generation.medium.as_ref(),
-            RoadType::Small => self.generation.small.as_ref(),
+        &self.class(*road_type).generation
+    }
+
+    // as above, but with any overrides from the zone containing the point
+    pub fn generation_at(&self, road_type: &RoadType, point: &Point) -> &GenerationConfig {
+        self.zone_at(point)
+            .and_then(|z| z.generation(&self.class(*road_type).name))
+            .unwrap_or_else(|| self.generation(road_type))
+    }
+
+    // the first zone with a shape containing the point, otherwise the first shapeless zone
+    pub fn zone_at(&self, point: &Point) -> Option<&ZoneConfig> {
+        self.zones
+            .iter()
+            .find(|z| z.shape.is_some() && z.contains(point))
+            .or_else(|| self.zones.iter().find(|z| z.shape.is_none()))
+    }
+
+    pub fn lots_at(&self, point: &Point) -> &LotConfig {
+        self.zone_at(point)
+            .and_then(|z| z.lots.as_ref())
+            .unwrap_or(&self.lots)
+    }
+
+    pub fn window_centre(&self) -> [f64; 2] {
+        [self.window.width as f64 / 2.0, self.window.height as f64 / 2.0]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_repo_config() {
+        let mut config: Config = toml::from_str(include_str!("../../config.toml")).unwrap();
+        config.resolve_classes().unwrap();
+
+        assert_eq!(config.classes().len(), 3);
+        assert_eq!(config.zones.len(), 3);
+
+        let radial = Point::new(200.0, 300.0);
+        let organic = Point::new(500.0, 100.0);
+        assert_eq!(config.zone_at(&radial).unwrap().rule, GenerationRule::Radial);
+        assert_eq!(config.zone_at(&organic).unwrap().rule, GenerationRule::Organic);
+        assert_eq!(config.zone_at(&Point::new(800.0, 100.0)).unwrap().rule,
+                   GenerationRule::Grid);
+        assert_eq!(config.zones[1].centre(), Some([500.0, 300.0]));
+    }
+
+    #[test]
+    fn overrides_every_generation_field() {
+        let overrides = GenerationOverride {
+            merge_range: Some(1.0),
+            fuel_range: Some([2, 3]),
+            road_chance: Some(0.4),
+            road_length: Some(5.0),
+            organic_angle: Some(6.0),
+            child_chance: Some(0.7),
+            delay: Some(8),
+            density_angle: Some(9.0),
+            max_bridge_length: Some(10.0),
+            max_slope: Some(0.11),
+            min_junction_angle: Some(12.0),
+            min_length: Some(13.0),
+            extend_range: Some(14.0),
+            width: Some(15.0),
+            lanes: Some(16),
+            speed_limit: Some(17.0),
+            surface: Some(Surface::Gravel),
+            building_chance: Some(0.18),
+            storeys: Some([19, 20]),
         };
+        let overridden = overrides.apply(&GenerationConfig::default());
+
+        assert_eq!(overridden.merge_range, 1.0);
+        assert_eq!(overridden.fuel_range, [2, 3]);
+        assert_eq!(overridden.road_chance, 0.4);
+        assert_eq!(overridden.road_length, 5.0);
+        assert_eq!(overridden.organic_angle, 6.0);
+        assert_eq!(overridden.child_chance, 0.7);
+        assert_eq!(overridden.delay, 8);
+        assert_eq!(overridden.density_angle, 9.0);
+        assert_eq!(overridden.max_bridge_length, Some(10.0));
+        assert_eq!(overridden.max_slope, Some(0.11));
+        assert_eq!(overridden.min_junction_angle, 12.0);
+        assert_eq!(overridden.min_length, 13.0);
+        assert_eq!(overridden.extend_range, 14.0);
+        assert_eq!(overridden.width, 15.0);
+        assert_eq!(overridden.lanes, 16);
+        assert_eq!(overridden.speed_limit, 17.0);
+        assert_eq!(overridden.surface, Surface::Gravel);
+        assert_eq!(overridden.building_chance, 0.18);
+        assert_eq!(overridden.storeys, [19, 20]);
+
+        // and nothing is overridden by default
+        let kept = GenerationOverride::default().apply(&overridden);
+        assert_eq!(kept.merge_range, 1.0);
+        assert_eq!(kept.storeys, [19, 20]);
+    }
+
+    #[test]
+    fn parses_polygon_zone() {
+        let mut zone: ZoneConfig = toml::from_str(r#"
+            rule = "grid"
+            shape = { kind = "polygon", points = [[0, 0], [10, 0], [0, 10]] }
+        "#)
+                .unwrap();
+        zone.resolve(&[]).unwrap();
 
-        gen.unwrap_or(&self.generation.large)
+        assert!(zone.contains(&Point::new(2.0, 2.0)));
+        assert!(!zone.contains(&Point::new(8.0, 8.0)));
     }
 }
@@ -0,0 +1,98 @@
+use {Point, RoadMap, Surface};
+use export::Projection;
+use serde_json;
+use std::io::{self, Write};
+
+#[derive(Serialize)]
+struct FeatureCollection {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    features: Vec<Feature>,
+}
+
+#[derive(Serialize)]
+struct Feature {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    // index of the road
+    id: usize,
+    geometry: LineString,
+    properties: Properties,
+}
+
+#[derive(Serialize)]
+struct LineString {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    coordinates: Vec<[f64; 2]>,
+}
+
+// lengths in metres if projected, otherwise world units
+#[derive(Serialize)]
+struct Properties {
+    // name of the road class
+    road_type: String,
+    fuel: u32,
+    bridge: bool,
+    planned: bool,
+    street: usize,
+    width: f64,
+    lanes: u32,
+    speed_limit: f64,
+    surface: Surface,
+    // at the start and end
+    elevation: Option<[f64; 2]>,
+}
+
+// each road as a line string feature, in longitude and latitude if projected
+pub fn write<W: Write>(roadmap: &RoadMap,
+                       projection: Option<&Projection>,
+                       out: W)
+                       -> io::Result<()> {
+    let position = |p: Point| match projection {
+        Some(projection) => projection.project(&p),
+        None => p.pos,
+    };
+    let length = |l: f64| match projection {
+        Some(projection) => projection.metres(l),
+        None => l,
+    };
+
+    let features = roadmap
+        .roads()
+        .iter()
+        .enumerate()
+        .map(|(id, road)| {
+            let (from, to) = road.points();
+            let profile = road.profile();
+
+            Feature {
+                kind: "Feature",
+                id,
+                geometry: LineString {
+                    kind: "LineString",
+                    coordinates: vec![position(from.unwrap()), position(to.unwrap())],
+                },
+                properties: Properties {
+                    road_type: roadmap.config().class(road.road_type()).name.clone(),
+                    fuel: road.fuel(),
+                    bridge: road.is_bridge(),
+                    planned: road.is_planned(),
+                    street: road.street(),
+                    width: length(profile.width),
+                    lanes: profile.lanes,
+                    speed_limit: profile.speed_limit,
+                    surface: profile.surface,
+                    elevation: road.elevation().map(|(a, b)| [length(a), length(b)]),
+                },
+            }
+        })
+        .collect();
+
+    let collection = FeatureCollection {
+        kind: "FeatureCollection",
+        features,
+    };
+
+    serde_json::to_writer(out, &collection).map_err(io::Error::from)
+}
@@ -0,0 +1,164 @@
+use mesh::Mesh;
+use serde_json;
+use std::io::{self, Write};
+
+// buffer view targets
+const ARRAY_BUFFER: u32 = 34962;
+const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+
+// accessor component types
+const FLOAT: u32 = 5126;
+const UNSIGNED_INT: u32 = 5125;
+
+const TRIANGLES: u32 = 4;
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+// glTF 2.0 with the buffer embedded, so everything is in the one file
+pub fn write<W: Write>(mesh: &Mesh, out: W) -> io::Result<()> {
+    let (positions, uvs, indices) = (mesh.positions(), mesh.uvs(), mesh.indices());
+
+    // accessors can't be empty
+    if indices.is_empty() {
+        let gltf = json!({
+            "asset": { "version": "2.0", "generator": "road_generation" },
+            "scene": 0,
+            "scenes": [{ "nodes": [] }],
+        });
+        return serde_json::to_writer(out, &gltf).map_err(io::Error::from);
+    }
+
+    // positions, then uvs, then indices, each already aligned to 4 bytes
+    let mut buffer = Vec::with_capacity(positions.len() * 12 + uvs.len() * 8 + indices.len() * 4);
+    for axis in positions.iter().flat_map(|p| p.iter()) {
+        buffer.extend_from_slice(&axis.to_le_bytes());
+    }
+    let uvs_offset = buffer.len();
+    for axis in uvs.iter().flat_map(|uv| uv.iter()) {
+        buffer.extend_from_slice(&axis.to_le_bytes());
+    }
+    let indices_offset = buffer.len();
+    for index in indices {
+        buffer.extend_from_slice(&index.to_le_bytes());
+    }
+
+    let (mut min, mut max) = (positions[0], positions[0]);
+    for p in positions {
+        for axis in 0..3 {
+            min[axis] = min[axis].min(p[axis]);
+            max[axis] = max[axis].max(p[axis]);
+        }
+    }
+
+    let gltf = json!({
+        "asset": { "version": "2.0", "generator": "road_generation" },
+        "scene": 0,
+        "scenes": [{ "nodes": [0] }],
+        "nodes": [{ "mesh": 0, "name": "roads" }],
+        "meshes": [{
+            "name": "roads",
+            "primitives": [{
+                "attributes": { "POSITION": 0, "TEXCOORD_0": 1 },
+                "indices": 2,
+                "material": 0,
+                "mode": TRIANGLES,
+            }],
+        }],
+        "materials": [{
+            "name": "road",
+            "pbrMetallicRoughness": {
+                "baseColorFactor": [0.3, 0.3, 0.3, 1.0],
+                "metallicFactor": 0.0,
+                "roughnessFactor": 1.0,
+            },
+        }],
+        "buffers": [{
+            "byteLength": buffer.len(),
+            "uri": format!("data:application/octet-stream;base64,{}", base64(&buffer)),
+        }],
+        "bufferViews": [
+            {
+                "buffer": 0,
+                "byteOffset": 0,
+                "byteLength": uvs_offset,
+                "target": ARRAY_BUFFER,
+            },
+            {
+                "buffer": 0,
+                "byteOffset": uvs_offset,
+                "byteLength": indices_offset - uvs_offset,
+                "target": ARRAY_BUFFER,
+            },
+            {
+                "buffer": 0,
+                "byteOffset": indices_offset,
+                "byteLength": buffer.len() - indices_offset,
+                "target": ELEMENT_ARRAY_BUFFER,
+            },
+        ],
+        "accessors": [
+            {
+                "bufferView": 0,
+                "componentType": FLOAT,
+                "count": positions.len(),
+                "type": "VEC3",
+                "min": min,
+                "max": max,
+            },
+            {
+                "bufferView": 1,
+                "componentType": FLOAT,
+                "count": uvs.len(),
+                "type": "VEC2",
+            },
+            {
+                "bufferView": 2,
+                "componentType": UNSIGNED_INT,
+                "count": indices.len(),
+                "type": "SCALAR",
+            },
+        ],
+    });
+
+    serde_json::to_writer(out, &gltf).map_err(io::Error::from)
+}
+
+// padded with =
+fn base64(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let byte = |i: usize| *chunk.get(i).unwrap_or(&0) as u32;
+        let group = (byte(0) << 16) | (byte(1) << 8) | byte(2);
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(BASE64[((group >> (18 - 6 * i)) & 63) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::base64;
+
+    #[test]
+    fn base64_matches_known_vectors() {
+        // rfc 4648
+        let vectors = [("", ""),
+                       ("f", "Zg=="),
+                       ("fo", "Zm8="),
+                       ("foo", "Zm9v"),
+                       ("foob", "Zm9vYg=="),
+                       ("fooba", "Zm9vYmE="),
+                       ("foobar", "Zm9vYmFy")];
+        for &(bytes, encoded) in &vectors {
+            assert_eq!(base64(bytes.as_bytes()), encoded);
+        }
+
+        assert_eq!(base64(&[0x00, 0xef, 0xff, 0xfe]), "AO///g==");
+    }
+}
@@ -0,0 +1,116 @@
+use {Point, RoadMap, RoadError};
+use config::ProjectionConfig;
+use mesh::Mesh;
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::{self, BufWriter};
+use std::path::Path;
+
+pub mod geojson;
+pub mod svg;
+pub mod osm;
+pub mod obj;
+pub mod gltf;
+
+// metres, mean
+const EARTH_RADIUS: f64 = 6_371_000.0;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ExportFormat {
+    GeoJson,
+    Svg,
+    Osm,
+    Obj,
+    Gltf,
+}
+
+impl ExportFormat {
+    // from the extension, none if unrecognised
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<ExportFormat> {
+        match path.as_ref().extension().and_then(|e| e.to_str()) {
+            Some("geojson") => Some(ExportFormat::GeoJson),
+            Some("svg") => Some(ExportFormat::Svg),
+            Some("osm") => Some(ExportFormat::Osm),
+            Some("obj") => Some(ExportFormat::Obj),
+            Some("gltf") => Some(ExportFormat::Gltf),
+            _ => None,
+        }
+    }
+}
+
+// world units to degrees, equirectangular around the middle of the map.
+// close enough over the size of a town
+#[derive(Debug, Copy, Clone)]
+pub struct Projection {
+    centre: Point,
+    origin: [f64; 2],
+    scale: f64,
+}
+
+impl Projection {
+    pub fn new(config: &ProjectionConfig, width: u32, height: u32) -> Projection {
+        Projection {
+            centre: Point::new(width as f64 / 2.0, height as f64 / 2.0),
+            origin: config.origin,
+            scale: config.scale,
+        }
+    }
+
+    // longitude then latitude. world y points south
+    pub fn project(&self, point: &Point) -> [f64; 2] {
+        let east = self.metres(point.x() - self.centre.x());
+        let north = self.metres(self.centre.y() - point.y());
+        let latitude = self.origin[0];
+
+        [self.origin[1] + (east / (EARTH_RADIUS * latitude.to_radians().cos())).to_degrees(),
+         latitude + (north / EARTH_RADIUS).to_degrees()]
+    }
+
+    pub fn metres(&self, length: f64) -> f64 {
+        length * self.scale
+    }
+}
+
+impl RoadMap {
+    // the projection from the export config, if any
+    pub fn projection(&self) -> Option<Projection> {
+        self.config
+            .export
+            .projection
+            .as_ref()
+            .map(|p| Projection::new(p, self.width(), self.height()))
+    }
+
+    // in metres if projected
+    fn projected_mesh(&self) -> Mesh {
+        let mut mesh = self.mesh();
+        if let Some(projection) = self.projection() {
+            mesh.scale(projection.metres(1.0));
+        }
+        mesh
+    }
+
+    pub fn export<P: AsRef<Path>>(&self, path: P, format: ExportFormat) -> Result<(), RoadError> {
+        let path = path.as_ref();
+        let failed = |e: io::Error| {
+            RoadError::Map(format!("Failed to export '{}': {}", path.display(), e))
+        };
+
+        let mut writer = BufWriter::new(File::create(path).map_err(failed)?);
+        let written = match format {
+            ExportFormat::GeoJson => geojson::write(self, self.projection().as_ref(), &mut writer),
+            ExportFormat::Svg => svg::write(self, &self.config.export.svg, &mut writer),
+            ExportFormat::Osm => {
+                // needs coordinates on the globe regardless
+                let projection = self.projection().unwrap_or_else(|| {
+                    Projection::new(&ProjectionConfig::default(), self.width(), self.height())
+                });
+                osm::write(self, &projection, &mut writer)
+            }
+            ExportFormat::Obj => obj::write(&self.projected_mesh(), &mut writer),
+            ExportFormat::Gltf => gltf::write(&self.projected_mesh(), &mut writer),
+        };
+
+        written.and_then(|_| writer.flush()).map_err(failed)
+    }
+}
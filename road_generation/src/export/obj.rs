@@ -0,0 +1,23 @@
+use mesh::Mesh;
+use std::io::{self, Write};
+
+// a single object with texture coordinates, indexed from 1
+pub fn write<W: Write>(mesh: &Mesh, mut out: W) -> io::Result<()> {
+    writeln!(out, "# road surfaces, y up")?;
+    writeln!(out, "o roads")?;
+
+    for p in mesh.positions() {
+        writeln!(out, "v {} {} {}", p[0], p[1], p[2])?;
+    }
+
+    for uv in mesh.uvs() {
+        writeln!(out, "vt {} {}", uv[0], uv[1])?;
+    }
+
+    for triangle in mesh.indices().chunks(3) {
+        let (a, b, c) = (triangle[0] + 1, triangle[1] + 1, triangle[2] + 1);
+        writeln!(out, "f {a}/{a} {b}/{b} {c}/{c}", a = a, b = b, c = c)?;
+    }
+
+    Ok(())
+}
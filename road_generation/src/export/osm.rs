@@ -0,0 +1,128 @@
+use {Point, Road, RoadMap, RoadType, Surface, NodeId};
+use config::Config;
+use export::Projection;
+use std::io::{self, Write};
+
+// the tag and its value
+type Tags = Vec<(&'static str, String)>;
+
+// nodes for every welded vertex, and ways along streets split wherever their tags change.
+// ids start at 1
+pub fn write<W: Write>(roadmap: &RoadMap, projection: &Projection, mut out: W) -> io::Result<()> {
+    let graph = roadmap.graph();
+    let node_at = |point: Option<Point>| graph.node_at(&point.unwrap()).unwrap();
+
+    let mut ways: Vec<(Vec<NodeId>, Tags)> = Vec::new();
+    for street in roadmap.streets() {
+        let mut current: Option<(Vec<NodeId>, Tags)> = None;
+
+        for &edge in street.roads() {
+            let road = roadmap.road(edge);
+            let tags = tags(roadmap.config(), road, projection);
+            let to = node_at(road.points().1);
+
+            let continues = current.as_ref().is_some_and(|(_, t)| *t == tags);
+            if continues {
+                current.as_mut().unwrap().0.push(to);
+            } else {
+                ways.extend(current.take());
+                current = Some((vec![node_at(road.points().0), to], tags));
+            }
+        }
+
+        ways.extend(current);
+    }
+
+    let positions: Vec<[f64; 2]> = graph
+        .nodes()
+        .iter()
+        .map(|node| projection.project(&node.position()))
+        .collect();
+
+    writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(out, r#"<osm version="0.6" generator="road_generation">"#)?;
+
+    if !positions.is_empty() {
+        let (mut min, mut max) = (positions[0], positions[0]);
+        for p in &positions {
+            min = [min[0].min(p[0]), min[1].min(p[1])];
+            max = [max[0].max(p[0]), max[1].max(p[1])];
+        }
+        writeln!(out,
+                 r#"  <bounds minlat="{:.7}" minlon="{:.7}" maxlat="{:.7}" maxlon="{:.7}"/>"#,
+                 min[1],
+                 min[0],
+                 max[1],
+                 max[0])?;
+    }
+
+    // positive ids need a version for editors to accept them as existing data
+    for (id, p) in positions.iter().enumerate() {
+        writeln!(out,
+                 r#"  <node id="{}" version="1" visible="true" lat="{:.7}" lon="{:.7}"/>"#,
+                 id + 1,
+                 p[1],
+                 p[0])?;
+    }
+
+    for (id, (nodes, tags)) in ways.iter().enumerate() {
+        writeln!(out, r#"  <way id="{}" version="1" visible="true">"#, id + 1)?;
+        for node in nodes {
+            writeln!(out, r#"    <nd ref="{}"/>"#, node.0 + 1)?;
+        }
+        for &(key, ref value) in tags {
+            writeln!(out, r#"    <tag k="{}" v="{}"/>"#, key, escape(value))?;
+        }
+        writeln!(out, "  </way>")?;
+    }
+
+    writeln!(out, "</osm>")
+}
+
+// the class's highway tag, otherwise from how important it is
+pub fn highway(config: &Config, road_type: RoadType) -> &str {
+    if let Some(ref highway) = config.class(road_type).highway {
+        return highway;
+    }
+
+    match road_type.0 {
+        0 => "primary",
+        1 => "secondary",
+        _ => "residential",
+    }
+}
+
+fn tags(config: &Config, road: &Road, projection: &Projection) -> Tags {
+    let profile = road.profile();
+    let surface = match profile.surface {
+        Surface::Asphalt => "asphalt",
+        Surface::Concrete => "concrete",
+        Surface::Cobbles => "sett",
+        Surface::Gravel => "gravel",
+        Surface::Dirt => "dirt",
+    };
+
+    let mut tags = vec![("highway", String::from(highway(config, road.road_type()))),
+                        ("lanes", profile.lanes.to_string()),
+                        ("maxspeed", (profile.speed_limit.round() as u32).to_string()),
+                        ("surface", String::from(surface))];
+
+    if profile.width > 0.0 {
+        tags.push(("width", format!("{:.1}", projection.metres(profile.width))));
+    }
+
+    if road.is_bridge() {
+        tags.push(("bridge", String::from("yes")));
+        tags.push(("layer", String::from("1")));
+    }
+
+    tags
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
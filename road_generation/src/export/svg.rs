@@ -0,0 +1,130 @@
+use {Point, RoadMap, GenerationRule};
+use config::{SvgConfig, ZoneConfig, ZoneShape};
+use std::cmp::Reverse;
+use std::io::{self, Write};
+
+// distance between points along the curve drawn for each street
+const CURVE_SPACING: f64 = 2.0;
+
+const VERTEX_RADIUS: f64 = 2.0;
+
+// in world units, drawn the same as the renderer
+pub fn write<W: Write>(roadmap: &RoadMap, config: &SvgConfig, mut out: W) -> io::Result<()> {
+    let (width, height) = (roadmap.width(), roadmap.height());
+    writeln!(out,
+             r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}">"#,
+             w = width,
+             h = height)?;
+    writeln!(out,
+             r#"<rect width="{}" height="{}" fill="rgb(240,240,255)"/>"#,
+             width,
+             height)?;
+
+    if config.zones {
+        writeln!(out, r#"<g fill-opacity="0.2">"#)?;
+        for zone in &roadmap.config().zones {
+            write_zone(zone, &mut out)?;
+        }
+        writeln!(out, "</g>")?;
+    }
+
+    if let Some(obstacles) = roadmap.obstacles() {
+        writeln!(out, r#"<g fill="rgb(100,150,230)">"#)?;
+        for polygon in obstacles.polygons() {
+            writeln!(out, r#"<polygon points="{}"/>"#, points(polygon))?;
+        }
+        writeln!(out, "</g>")?;
+    }
+
+    if config.blocks {
+        writeln!(out,
+                 r#"<g fill="rgb(220,220,220)" stroke="rgb(180,180,180)" stroke-width="0.5">"#)?;
+        for block in roadmap.blocks() {
+            writeln!(out, r#"<polygon points="{}"/>"#, points(block.polygon()))?;
+        }
+        writeln!(out, "</g>")?;
+    }
+
+    // least important first, so the rest are drawn over them
+    let mut streets = roadmap.streets();
+    streets.sort_by_key(|street| Reverse(street.road_type().0));
+
+    writeln!(out,
+             r#"<g fill="none" stroke-linecap="round" stroke-linejoin="round">"#)?;
+    for street in &streets {
+        let [r, g, b] = roadmap.config().class(street.road_type()).colour;
+        let width = roadmap.road(street.roads()[0]).width().max(1.0);
+        writeln!(out,
+                 r#"<polyline points="{}" stroke="rgb({},{},{})" stroke-width="{:.2}"/>"#,
+                 points(&street.sample(CURVE_SPACING)),
+                 r,
+                 g,
+                 b,
+                 width)?;
+    }
+    writeln!(out, "</g>")?;
+
+    if config.vertices {
+        writeln!(out, r#"<g fill="rgb(70,200,150)" fill-opacity="0.6">"#)?;
+        for node in roadmap.graph().nodes() {
+            let position = node.position();
+            writeln!(out,
+                     r#"<circle cx="{:.2}" cy="{:.2}" r="{}"/>"#,
+                     position.x(),
+                     position.y(),
+                     VERTEX_RADIUS)?;
+        }
+        writeln!(out, "</g>")?;
+    }
+
+    writeln!(out, "</svg>")
+}
+
+// shapeless zones cover everywhere else, so aren't drawn
+fn write_zone<W: Write>(zone: &ZoneConfig, out: &mut W) -> io::Result<()> {
+    let (rule, colour) = match zone.rule {
+        GenerationRule::Grid => ("grid", "rgb(255,170,0)"),
+        GenerationRule::Radial => ("radial", "rgb(170,0,255)"),
+        GenerationRule::Organic => ("organic", "rgb(0,170,0)"),
+    };
+
+    match zone.shape {
+        None => Ok(()),
+        Some(ZoneShape::Rect { x, y, width, height }) => {
+            writeln!(out,
+                     r#"<rect x="{:.2}" y="{:.2}" width="{:.2}" height="{:.2}" fill="{}"><title>{}</title></rect>"#,
+                     x,
+                     y,
+                     width,
+                     height,
+                     colour,
+                     rule)
+        }
+        Some(ZoneShape::Circle { centre, radius }) => {
+            writeln!(out,
+                     r#"<circle cx="{:.2}" cy="{:.2}" r="{:.2}" fill="{}"><title>{}</title></circle>"#,
+                     centre[0],
+                     centre[1],
+                     radius,
+                     colour,
+                     rule)
+        }
+        Some(ZoneShape::Polygon { points: ref polygon }) => {
+            let polygon: Vec<Point> = polygon.iter().map(|&p| Point::from(p)).collect();
+            writeln!(out,
+                     r#"<polygon points="{}" fill="{}"><title>{}</title></polygon>"#,
+                     points(&polygon),
+                     colour,
+                     rule)
+        }
+    }
+}
+
+// fixed precision, so the same map is always written the same
+fn points(points: &[Point]) -> String {
+    points
+        .iter()
+        .map(|p| format!("{:.2},{:.2}", p.x(), p.y()))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
@@ -0,0 +1,272 @@
+use RoadError;
+use config::FieldConfig;
+use std::fs::File;
+use std::io::prelude::*;
+
+// a value in [0, 1] at every point of the map
+pub trait ScalarField {
+    fn sample(&self, x: f64, y: f64) -> f64;
+}
+
+impl<F: Fn(f64, f64) -> f64> ScalarField for F {
+    fn sample(&self, x: f64, y: f64) -> f64 {
+        self(x, y)
+    }
+}
+
+pub struct Constant(pub f64);
+
+impl ScalarField for Constant {
+    fn sample(&self, _x: f64, _y: f64) -> f64 {
+        self.0
+    }
+}
+
+// fractal value noise, deterministic for a given seed
+pub struct ValueNoise {
+    seed: u64,
+    scale: f64,
+    octaves: u32,
+}
+
+impl ValueNoise {
+    pub fn new(seed: u64, scale: f64, octaves: u32) -> ValueNoise {
+        ValueNoise {
+            seed,
+            scale: scale.max(1e-6),
+            octaves: octaves.max(1),
+        }
+    }
+
+    fn lattice(&self, x: i64, y: i64, octave: u32) -> f64 {
+        let mut h = self.seed ^ u64::from(octave).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+        h ^= (x as u64).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        h = h.rotate_left(31) ^ (y as u64).wrapping_mul(0x94d0_49bb_1331_11eb);
+
+        // splitmix64 finaliser
+        h = (h ^ (h >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        h = (h ^ (h >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        h ^= h >> 31;
+
+        (h >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn octave(&self, x: f64, y: f64, octave: u32) -> f64 {
+        let (x0, y0) = (x.floor(), y.floor());
+        let (tx, ty) = (smoothstep(x - x0), smoothstep(y - y0));
+        let (x0, y0) = (x0 as i64, y0 as i64);
+
+        let top = lerp(self.lattice(x0, y0, octave), self.lattice(x0 + 1, y0, octave), tx);
+        let bottom = lerp(self.lattice(x0, y0 + 1, octave),
+                          self.lattice(x0 + 1, y0 + 1, octave),
+                          tx);
+        lerp(top, bottom, ty)
+    }
+}
+
+impl ScalarField for ValueNoise {
+    fn sample(&self, x: f64, y: f64) -> f64 {
+        let mut total = 0.0;
+        let mut amplitude = 1.0;
+        let mut frequency = 1.0 / self.scale;
+        let mut max = 0.0;
+
+        for octave in 0..self.octaves {
+            total += self.octave(x * frequency, y * frequency, octave) * amplitude;
+            max += amplitude;
+            amplitude *= 0.5;
+            frequency *= 2.0;
+        }
+
+        total / max
+    }
+}
+
+// greyscale image stretched over the map, white is 1
+pub struct GreyImage {
+    width: usize,
+    height: usize,
+    pixels: Vec<f64>,
+
+    // world units to pixels
+    scale: [f64; 2],
+}
+
+impl GreyImage {
+    // binary (P5) or plain (P2) netpbm greymap
+    pub fn load_pgm(path: &str, map_width: u32, map_height: u32) -> Result<GreyImage, RoadError> {
+        let mut bytes = Vec::new();
+        File::open(path)
+            .and_then(|mut f| f.read_to_end(&mut bytes))
+            .map_err(|e| RoadError::Settings(format!("Failed to read image '{}': {}", path, e)))?;
+
+        GreyImage::parse_pgm(&bytes, map_width, map_height)
+            .map_err(|e| RoadError::Settings(format!("Invalid image '{}': {}", path, e)))
+    }
+
+    fn parse_pgm(bytes: &[u8], map_width: u32, map_height: u32) -> Result<GreyImage, String> {
+        let mut cursor = 0;
+        let magic = next_token(bytes, &mut cursor).ok_or("missing header")?;
+        let binary = match magic {
+            "P5" => true,
+            "P2" => false,
+            other => return Err(format!("unsupported format {}", other)),
+        };
+
+        let mut header = [0usize; 3];
+        for value in &mut header {
+            *value = next_token(bytes, &mut cursor)
+                .and_then(|t| t.parse().ok())
+                .ok_or("malformed header")?;
+        }
+
+        let [width, height, max] = header;
+        if width == 0 || height == 0 || max == 0 || max > 0xffff {
+            return Err(String::from("malformed header"));
+        }
+
+        let count = width * height;
+        let pixels: Vec<f64> = if binary {
+            // single whitespace byte after the header
+            let data = &bytes[(cursor + 1).min(bytes.len())..];
+            let depth = if max < 256 { 1 } else { 2 };
+            if data.len() < count * depth {
+                return Err(String::from("truncated pixel data"));
+            }
+
+            data.chunks(depth)
+                .take(count)
+                .map(|px| px.iter().fold(0usize, |acc, &b| (acc << 8) | b as usize))
+                .map(|v| v as f64 / max as f64)
+                .collect()
+        } else {
+            let mut pixels = Vec::with_capacity(count);
+            for _ in 0..count {
+                let value: usize = next_token(bytes, &mut cursor)
+                    .and_then(|t| t.parse().ok())
+                    .ok_or("truncated pixel data")?;
+                pixels.push(value as f64 / max as f64);
+            }
+            pixels
+        };
+
+        Ok(GreyImage {
+               width,
+               height,
+               pixels,
+               scale: [width as f64 / f64::from(map_width.max(1)),
+                       height as f64 / f64::from(map_height.max(1))],
+           })
+    }
+
+    fn pixel(&self, x: isize, y: isize) -> f64 {
+        let x = x.max(0).min(self.width as isize - 1) as usize;
+        let y = y.max(0).min(self.height as isize - 1) as usize;
+        self.pixels[y * self.width + x]
+    }
+}
+
+impl ScalarField for GreyImage {
+    // bilinear
+    fn sample(&self, x: f64, y: f64) -> f64 {
+        let px = x * self.scale[0] - 0.5;
+        let py = y * self.scale[1] - 0.5;
+        let (x0, y0) = (px.floor(), py.floor());
+        let (tx, ty) = (px - x0, py - y0);
+        let (x0, y0) = (x0 as isize, y0 as isize);
+
+        let top = lerp(self.pixel(x0, y0), self.pixel(x0 + 1, y0), tx);
+        let bottom = lerp(self.pixel(x0, y0 + 1), self.pixel(x0 + 1, y0 + 1), tx);
+        lerp(top, bottom, ty)
+    }
+}
+
+// builds the configured field, sized to the map
+pub fn from_config(config: &FieldConfig,
+                   seed: u64,
+                   map_width: u32,
+                   map_height: u32)
+                   -> Result<Box<dyn ScalarField>, RoadError> {
+    Ok(match *config {
+           FieldConfig::Constant { value } => Box::new(Constant(value)),
+           FieldConfig::Noise { scale, octaves } => Box::new(ValueNoise::new(seed, scale, octaves)),
+           FieldConfig::Image { ref path } => {
+               Box::new(GreyImage::load_pgm(path, map_width, map_height)?)
+           }
+       })
+}
+
+// whitespace separated, skipping # comments
+fn next_token<'a>(bytes: &'a [u8], cursor: &mut usize) -> Option<&'a str> {
+    loop {
+        while *cursor < bytes.len() && (bytes[*cursor] as char).is_whitespace() {
+            *cursor += 1;
+        }
+
+        if *cursor < bytes.len() && bytes[*cursor] == b'#' {
+            while *cursor < bytes.len() && bytes[*cursor] != b'\n' {
+                *cursor += 1;
+            }
+        } else {
+            break;
+        }
+    }
+
+    let start = *cursor;
+    while *cursor < bytes.len() && !(bytes[*cursor] as char).is_whitespace() {
+        *cursor += 1;
+    }
+
+    if start == *cursor {
+        None
+    } else {
+        ::std::str::from_utf8(&bytes[start..*cursor]).ok()
+    }
+}
+
+#[inline]
+fn lerp(a: f64, b: f64, t: f64) -> f64 {
+    a + (b - a) * t
+}
+
+#[inline]
+fn smoothstep(t: f64) -> f64 {
+    t * t * (3.0 - 2.0 * t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_plain_pgm() {
+        let bytes = b"P2\n# a comment\n2 2\n255\n0 255\n51 102\n";
+        let image = GreyImage::parse_pgm(bytes, 4, 2).unwrap();
+        assert_eq!((image.width, image.height), (2, 2));
+        assert_eq!(image.pixels, vec![0.0, 1.0, 0.2, 0.4]);
+        assert_eq!(image.scale, [0.5, 1.0]);
+    }
+
+    #[test]
+    fn parses_binary_pgm() {
+        // the first pixel is a whitespace byte, which must not be skipped as part of the header
+        let mut bytes = b"P5 2 2 255\n".to_vec();
+        bytes.extend_from_slice(&[10, 255, 0, 51]);
+        let image = GreyImage::parse_pgm(&bytes, 2, 2).unwrap();
+        assert_eq!(image.pixels, vec![10.0 / 255.0, 1.0, 0.0, 0.2]);
+
+        // two bytes a pixel, most significant first
+        let mut bytes = b"P5\n1 2\n65535\n".to_vec();
+        bytes.extend_from_slice(&[0xff, 0xff, 0x00, 0x00]);
+        let image = GreyImage::parse_pgm(&bytes, 1, 2).unwrap();
+        assert_eq!(image.pixels, vec![1.0, 0.0]);
+    }
+
+    #[test]
+    fn rejects_bad_pgm() {
+        assert!(GreyImage::parse_pgm(b"P3\n1 1\n255\n0\n", 1, 1).is_err());
+        assert!(GreyImage::parse_pgm(b"P2\n2 2\n255\n0 1 2\n", 1, 1).is_err());
+        assert!(GreyImage::parse_pgm(b"P5\n2 2\n255\n\x00\x01", 1, 1).is_err());
+        assert!(GreyImage::parse_pgm(b"P2\n0 2\n255\n", 1, 1).is_err());
+    }
+}
@@ -0,0 +1,68 @@
+use Road;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+// proposed roads waiting to be evaluated, earliest time first.
+// roads due at the same time are popped in the order they were pushed
+#[derive(Default)]
+pub struct Frontier {
+    heap: BinaryHeap<Entry>,
+    pushed: u64,
+}
+
+struct Entry {
+    time: u64,
+    order: u64,
+    road: Road,
+}
+
+impl Frontier {
+    pub fn new() -> Frontier {
+        Frontier::default()
+    }
+
+    pub fn push(&mut self, time: u64, road: Road) {
+        self.heap.push(Entry {
+                           time,
+                           order: self.pushed,
+                           road,
+                       });
+        self.pushed += 1;
+    }
+
+    // returns (time, road)
+    pub fn pop(&mut self) -> Option<(u64, Road)> {
+        self.heap.pop().map(|e| (e.time, e.road))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.heap.is_empty()
+    }
+}
+
+impl Entry {
+    fn key(&self) -> (u64, u64) {
+        (self.time, self.order)
+    }
+}
+
+impl PartialEq for Entry {
+    fn eq(&self, other: &Entry) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for Entry {}
+
+impl PartialOrd for Entry {
+    fn partial_cmp(&self, other: &Entry) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Entry {
+    // reversed, BinaryHeap is a max-heap
+    fn cmp(&self, other: &Entry) -> Ordering {
+        other.key().cmp(&self.key())
+    }
+}
@@ -1,9 +1,20 @@
 use kdtree::kdtree::*;
-use {Point, Road, RoadType, RoadMap, RoadError};
-use config::Config;
+use {Point, Road, RoadType, RoadProfile, Surface, RoadMap, RoadError, RoadGraph, NodeId, EdgeId};
+use config::{Config, DensityConfig, FrontierConfig, HighwayConfig, CleanupConfig};
+use field::{self, ScalarField};
+use obstacles::ObstacleMap;
+use terrain::Heightmap;
+use blocks::{self, Block};
+use lots::{self, Lot};
+use buildings::{self, Building};
+use highways;
+use streets::{self, Street};
+use mesh::{self, Mesh};
 use rules;
-use rand::{thread_rng, Rng};
-use std::collections::VecDeque;
+use geometry;
+use rand::{thread_rng, Rng, SeedableRng, XorShiftRng};
+use std::f64::consts::{PI, FRAC_PI_4};
+use frontier::Frontier;
 
 impl Point {
     pub fn new(x: f64, y: f64) -> Point {
@@ -38,6 +49,18 @@ impl From<[f64; 2]> for Point {
     }
 }
 
+impl Default for RoadProfile {
+    // a two lane line
+    fn default() -> RoadProfile {
+        RoadProfile {
+            width: 0.0,
+            lanes: 2,
+            speed_limit: 50.0,
+            surface: Surface::default(),
+        }
+    }
+}
+
 impl Road {
     pub fn new_with_points(road_type: RoadType, from: Point, to: Point) -> Road {
         Road {
@@ -45,6 +68,11 @@ impl Road {
             from: Some(from),
             to: Some(to),
             fuel: 1,
+            bridge: false,
+            elevation: None,
+            planned: false,
+            street: 0,
+            profile: RoadProfile::default(),
         }
     }
     pub fn new(road_type: RoadType) -> Road {
@@ -53,6 +81,11 @@ impl Road {
             from: None,
             to: None,
             fuel: 1,
+            bridge: false,
+            elevation: None,
+            planned: false,
+            street: 0,
+            profile: RoadProfile::default(),
         }
     }
 
@@ -94,35 +127,401 @@ impl Road {
     pub fn fuel(&self) -> u32 {
         self.fuel
     }
+
+    // crosses an obstacle
+    pub fn is_bridge(&self) -> bool {
+        self.bridge
+    }
+
+    // a highway planned between centres
+    pub fn is_planned(&self) -> bool {
+        self.planned
+    }
+
+    // roads continuing on from each other share a street
+    pub fn street(&self) -> usize {
+        self.street
+    }
+
+    pub fn profile(&self) -> &RoadProfile {
+        &self.profile
+    }
+
+    pub fn width(&self) -> f64 {
+        self.profile.width
+    }
+
+    // (from, to), only known once added to a map with terrain
+    pub fn elevation(&self) -> Option<(f64, f64)> {
+        self.elevation
+    }
+
+    pub fn length(&self) -> f64 {
+        let (from, to) = (self.from.unwrap(), self.to.unwrap());
+        (to.x() - from.x()).hypot(to.y() - from.y())
+    }
+
+    // radians
+    pub fn angle(&self) -> f64 {
+        let (from, to) = (self.from.unwrap(), self.to.unwrap());
+        (to.y() - from.y()).atan2(to.x() - from.x())
+    }
+
+    // keeps the start point
+    fn redirect(&mut self, angle: f64, length: f64) {
+        let to = geometry::project(&self.from.unwrap(), angle, length);
+        self.set_to(to);
+    }
+}
+
+
+// the density field, obstacles and terrain
+type Layers = (Option<Box<dyn ScalarField>>, Option<ObstacleMap>, Option<Heightmap>);
+
+// each layer from different noise
+fn create_layers(config: &Config, seed: u64) -> Result<Layers, RoadError> {
+    let density = match config.density.as_ref().and_then(|d| d.field.as_ref()) {
+        Some(field) => {
+            Some(field::from_config(field, seed, config.window.width, config.window.height)?)
+        }
+        None => None,
+    };
+
+    let obstacles = match config.obstacles {
+        Some(ref obstacles) => {
+            Some(ObstacleMap::from_config(obstacles,
+                                          seed.wrapping_add(1),
+                                          config.window.width,
+                                          config.window.height)?)
+        }
+        None => None,
+    };
+
+    let terrain = match config.terrain {
+        Some(ref terrain) => {
+            Some(Heightmap::from_config(terrain,
+                                        seed.wrapping_add(2),
+                                        config.window.width,
+                                        config.window.height)?)
+        }
+        None => None,
+    };
+
+    Ok((density, obstacles, terrain))
+}
+
+fn create_rng(seed: u64) -> XorShiftRng {
+    // splitmix64, otherwise the first few numbers from nearby seeds are nearly the same
+    let mut mixed = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    mixed = (mixed ^ (mixed >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    mixed = (mixed ^ (mixed >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    mixed ^= mixed >> 31;
+
+    let lo = mixed as u32;
+    let hi = (mixed >> 32) as u32;
+
+    // xorshift must not be seeded with all zeroes
+    XorShiftRng::from_seed([lo, hi, lo ^ 0x9e37_79b9, hi ^ 0x7f4a_7c15])
+}
+
+// where a bridge over the given obstructions along the road would end, if short enough
+fn bridge_end(obstacles: &ObstacleMap,
+              road: &Road,
+              intervals: &[(f64, f64)],
+              max_length: f64)
+              -> Option<Point> {
+    // distance to go beyond the far shore
+    const SHORE_MARGIN: f64 = 1.0;
+
+    let length = road.length();
+    if intervals.iter().any(|&(start, end)| (end - start) * length > max_length) {
+        return None;
+    }
+
+    let to = road.to.unwrap();
+    let (start, end) = *intervals.last().unwrap();
+    if end < 1.0 {
+        // already ends on land
+        return Some(to);
+    }
+
+    // extend to the far shore
+    let angle = road.angle();
+    let remaining = max_length - (end - start) * length;
+    let furthest = geometry::project(&to, angle, remaining);
+    let (_, far_shore) = *obstacles.intervals(&to, &furthest).first()?;
+    if far_shore >= 1.0 {
+        return None;
+    }
+
+    let end = geometry::project(&geometry::lerp(&to, &furthest, far_shore), angle, SHORE_MARGIN);
+    if obstacles.contains(&end) {
+        None
+    } else {
+        Some(end)
+    }
 }
 
+// tries for each random centre before giving up on it
+const CENTRE_ATTEMPTS: u32 = 100;
+
+// the initial roads, and the centres they start from
+fn create_frontier(config: &Config,
+                   obstacles: Option<&ObstacleMap>,
+                   rng: &mut XorShiftRng)
+                   -> Result<(Vec<Road>, Vec<Point>), RoadError> {
+    let frontier = config
+        .frontier
+        .clone()
+        .unwrap_or_else(FrontierConfig::single_centre);
+
+    let mut roads: Vec<Road> = Vec::new();
+    let mut centres: Vec<Point> = Vec::new();
+
+    let mut seed_road = |road_type: RoadType, from: Point, heading: f64| {
+        let length = config.generation(&road_type).road_length;
+        let to = geometry::project(&from, heading, length);
+        roads.push(Road::new_with_points(road_type, from, to));
+    };
+
+    for road in &frontier.roads {
+        let from = Point::new(road.position[0], road.position[1]);
+        seed_road(class_named(config, Some(&road.class))?, from, road.heading.to_radians());
+        centres.push(from);
+    }
+
+    let road_type = class_named(config, frontier.class.as_ref())?;
 
-fn create_frontier(config: &Config) -> Vec<Road> {
-    let mut vec: Vec<Road> = Vec::new();
-    let mut rng = thread_rng();
+    let (width, height) = (config.window.width as f64, config.window.height as f64);
+    for _ in 0..frontier.centres {
+        let candidate = (0..CENTRE_ATTEMPTS)
+            .map(|_| Point::new(rng.gen_range(0.0, width), rng.gen_range(0.0, height)))
+            .find(|p| {
+                      !obstacles.is_some_and(|o| o.contains(p)) &&
+                      centres
+                          .iter()
+                          .all(|c| geometry::distance(c, p) >= frontier.min_spacing)
+                  });
 
-    let road_type = RoadType::Large;
-    let a = Point::new(rng.gen_range(0.0, config.window.width as f64),
-                       rng.gen_range(0.0, config.window.height as f64));
-    let b = Point::new(a.x() + config.generation(&road_type).road_length, a.y());
+        // too crowded, so fewer centres than asked for
+        let centre = match candidate {
+            Some(centre) => centre,
+            None => break,
+        };
 
-    let road = Road::new_with_points(RoadType::Medium, a, b);
-    vec.push(road);
+        let heading = match frontier.heading {
+            Some(heading) => heading.to_radians(),
+            None => rng.gen_range(0.0, 2.0 * PI),
+        };
 
-    vec
+        seed_road(road_type, centre, heading);
+        centres.push(centre);
+    }
+
+    if roads.is_empty() {
+        return Err(RoadError::Settings(String::from("No roads to start generating from")));
+    }
+
+    Ok((roads, centres))
+}
+
+// the named road class, or the first if not specified
+fn class_named(config: &Config, name: Option<&String>) -> Result<RoadType, RoadError> {
+    match name {
+        Some(name) => {
+            config
+                .road_type(name)
+                .ok_or_else(|| RoadError::Settings(format!("Unknown road class '{}'", name)))
+        }
+        None => Ok(RoadType(0)),
+    }
+}
+
+// roads along highways routed between the centres
+fn plan_highways(config: &Config,
+                 highways: &HighwayConfig,
+                 centres: &[Point],
+                 obstacles: Option<&ObstacleMap>)
+                 -> Result<Vec<Road>, RoadError> {
+    let road_type = class_named(config, highways.class.as_ref())?;
+    let length = config.generation(&road_type).road_length;
+    let routes = highways::plan(centres,
+                                highways,
+                                obstacles,
+                                config.window.width,
+                                config.window.height);
+
+    let mut roads = Vec::new();
+    for (street, route) in routes.iter().enumerate() {
+        for leg in route.windows(2) {
+            plan_leg(leg, road_type, street, length, &mut roads);
+        }
+    }
+
+    Ok(roads)
+}
+
+// split into roads no longer than the given length
+fn plan_leg(leg: &[Point],
+            road_type: RoadType,
+            street: usize,
+            length: f64,
+            out: &mut Vec<Road>) {
+    let steps = (geometry::distance(&leg[0], &leg[1]) / length).ceil().max(1.0) as usize;
+    for i in 0..steps {
+        let from = geometry::lerp(&leg[0], &leg[1], i as f64 / steps as f64);
+
+        // exactly, so the highway meets the centre
+        let to = if i + 1 == steps {
+            leg[1]
+        } else {
+            geometry::lerp(&leg[0], &leg[1], (i + 1) as f64 / steps as f64)
+        };
+
+        let mut road = Road::new_with_points(road_type, from, to);
+        road.planned = true;
+        road.street = street;
+        out.push(road);
+    }
 }
 
 impl RoadMap {
     pub fn new(config: Config) -> Result<RoadMap, RoadError> {
-        let frontier = create_frontier(&config);
-        Ok(RoadMap::with_frontier(config, frontier))
+        let seed = config.seed.unwrap_or_else(|| thread_rng().gen());
+        RoadMap::with_seed(config, seed)
+    }
+
+    pub fn with_seed(config: Config, seed: u64) -> Result<RoadMap, RoadError> {
+        if config.classes().is_empty() {
+            return Err(RoadError::Settings(String::from("No road classes")));
+        }
+
+        let (density, obstacles, terrain) = create_layers(&config, seed)?;
+
+        let mut rng = create_rng(seed);
+        let (mut frontier, centres) = create_frontier(&config, obstacles.as_ref(), &mut rng)?;
+
+        // laid before anything else grows
+        if let Some(ref highways) = config.highways {
+            let mut planned = plan_highways(&config, highways, &centres, obstacles.as_ref())?;
+            planned.append(&mut frontier);
+            frontier = planned;
+        }
+
+        let mut roadmap = RoadMap::with_frontier(config, frontier, seed, rng);
+        roadmap.centres = centres;
+        roadmap.density = density;
+        roadmap.obstacles = obstacles;
+        roadmap.terrain = terrain;
+        Ok(roadmap)
+    }
+
+    // a finished map of roads grown from the config and seed, such as one loaded from a file
+    pub(crate) fn with_roads(config: Config,
+                             seed: u64,
+                             roads: Vec<Road>,
+                             centres: Vec<Point>)
+                             -> Result<RoadMap, RoadError> {
+        let (density, obstacles, terrain) = create_layers(&config, seed)?;
 
+        let mut roadmap = RoadMap::with_frontier(config, Vec::new(), seed, create_rng(seed));
+        roadmap.next_street = roads.iter().map(|road| road.street + 1).max().unwrap_or(0);
+        roadmap.rebuild(roads);
+        // already cleaned up before it was saved, if at all
+        roadmap.cleaned = true;
+        roadmap.centres = centres;
+        roadmap.density = density;
+        roadmap.obstacles = obstacles;
+        roadmap.terrain = terrain;
+        Ok(roadmap)
+    }
+
+    // overrides any density field from the config
+    pub fn set_density<F: ScalarField + 'static>(&mut self, density: F) {
+        self.density = Some(Box::new(density));
+    }
+
+    // overrides any obstacles from the config
+    pub fn set_obstacles(&mut self, obstacles: ObstacleMap) {
+        self.obstacles = Some(obstacles);
+    }
+
+    pub fn obstacles(&self) -> Option<&ObstacleMap> {
+        self.obstacles.as_ref()
+    }
+
+    // overrides any terrain from the config
+    pub fn set_terrain(&mut self, terrain: Heightmap) {
+        self.terrain = Some(terrain);
+    }
+
+    pub fn terrain(&self) -> Option<&Heightmap> {
+        self.terrain.as_ref()
     }
 
     pub fn roads(&self) -> &Vec<Road> {
         &self.roads
     }
 
+    pub fn road(&self, edge: EdgeId) -> &Road {
+        &self.roads[edge.0]
+    }
+
+    // rebuilt by each cleanup, so ids taken before one no longer refer to the same roads
+    pub fn graph(&self) -> &RoadGraph {
+        &self.graph
+    }
+
+    // chains of roads continuing on from each other
+    pub fn streets(&self) -> Vec<Street> {
+        streets::trace(&self.roads)
+    }
+
+    // areas enclosed by roads
+    pub fn blocks(&self) -> Vec<Block> {
+        blocks::extract(&self.graph)
+    }
+
+    // the road surfaces, joined at junctions
+    pub fn mesh(&self) -> Mesh {
+        mesh::build(&self.graph, &self.roads)
+    }
+
+    // every block split into lots, using the lot config of the zone each block is in
+    pub fn lots(&self) -> Vec<Lot> {
+        self.blocks()
+            .iter()
+            .flat_map(|b| lots::subdivide(b, self.config.lots_at(&b.centre())))
+            .collect()
+    }
+
+    // a building on each lot, depending on the type of road it faces
+    pub fn buildings(&self) -> Vec<Building> {
+        // independent of generation, so the same for every call
+        let mut rng = create_rng(self.seed.wrapping_add(3));
+
+        self.lots()
+            .iter()
+            .filter_map(|lot| {
+                let road_type = self.road(lot.road()).road_type();
+                let config = self.config.generation_at(&road_type, &lot.frontage().0);
+                if rng.next_f64() >= config.building_chance {
+                    return None;
+                }
+
+                buildings::generate(lot,
+                                    &self.roads,
+                                    &self.config.buildings,
+                                    config.storeys,
+                                    &mut rng)
+            })
+            .collect()
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
     pub fn width(&self) -> u32 {
         self.config.window.width
     }
@@ -131,22 +530,70 @@ impl RoadMap {
         self.config.window.height
     }
 
-    fn with_frontier(config: Config, frontier: Vec<Road>) -> RoadMap {
-        let mut frontier_points = frontier
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    // where the initial roads started
+    pub fn centres(&self) -> &[Point] {
+        &self.centres
+    }
+
+    // changes whenever ids are invalidated by a cleanup
+    pub fn cleanups(&self) -> u32 {
+        self.cleanups
+    }
+
+    // nothing left to propose
+    pub fn is_finished(&self) -> bool {
+        self.frontier.is_empty()
+    }
+
+    fn with_frontier(config: Config,
+                     frontier: Vec<Road>,
+                     seed: u64,
+                     rng: XorShiftRng)
+                     -> RoadMap {
+        // only the starts, so the initial roads don't merge into each other's ends
+        let mut frontier_points: Vec<Point> = frontier.iter().filter_map(|road| road.from).collect();
+
+        // highways already have a street for each route, everything else starts its own
+        let mut next_street = frontier
             .iter()
-            .fold(Vec::with_capacity(frontier.len() * 2), |mut acc, road| {
-                road.from.map_or((), |p| acc.push(p));
-                road.to.map_or((), |p| acc.push(p));
-                acc
-            });
+            .filter(|road| road.planned)
+            .map(|road| road.street + 1)
+            .max()
+            .unwrap_or(0);
+
+        // the kdtree can't be empty, nothing grows without a frontier anyway
+        if frontier_points.is_empty() {
+            frontier_points.push(Point::out_of_range());
+        }
 
-        frontier_points.pop();
+        let mut queue = Frontier::new();
+        for mut road in frontier {
+            if !road.planned {
+                road.street = next_street;
+                next_street += 1;
+            }
+            queue.push(0, road);
+        }
 
         RoadMap {
-            frontier: VecDeque::from(frontier),
+            frontier: queue,
             config: config,
             roads: Vec::new(),
+            graph: RoadGraph::new(),
             kdtree: Kdtree::new(&mut frontier_points),
+            seed,
+            rng,
+            density: None,
+            obstacles: None,
+            terrain: None,
+            centres: Vec::new(),
+            next_street,
+            cleanups: 0,
+            cleaned: false,
         }
     }
 
@@ -160,12 +607,12 @@ impl RoadMap {
                 count -= 1;
             }
 
-            let popped = self.frontier.pop_front();
+            let popped = self.frontier.pop();
             if popped.is_none() {
                 break;
             }
 
-            let mut road = popped.unwrap();
+            let (time, mut road) = popped.unwrap();
 
             let (accepted, did_merge) = self.accept_local_constraints(&mut road);
             if !accepted {
@@ -178,12 +625,14 @@ impl RoadMap {
                 continue;
             }
 
-            // propose some more
-            if !did_merge {
-                let branch = road.take_fuel();
+            // propose some more, planned roads meet the next part of their highway
+            if !did_merge || road.planned {
+                let branch = road.planned || road.take_fuel();
                 let mut proposed = self.propose_with_global_goals(&road, branch);
+                self.continue_street(&road, &mut proposed);
                 for r in proposed.drain(..) {
-                    self.frontier.push_back(r);
+                    let delay = self.config.generation_at(&r.road_type(), &r.from.unwrap()).delay;
+                    self.frontier.push(time + delay as u64, r);
                 }
             }
 
@@ -191,20 +640,213 @@ impl RoadMap {
             self.add_road(road);
         }
 
+        // once growth finishes, however the last proposal went
+        if self.frontier.is_empty() && !self.cleaned {
+            self.cleaned = true;
+            if let Some(cleanup) = self.config.cleanup.clone() {
+                self.clean_up(&cleanup);
+            }
+        }
+
         Ok(())
     }
 
-    fn add_road(&mut self, road: Road) {
+    // tidies up the finished roads, renumbering them and the graph. any NodeId or EdgeId
+    // from before is invalid afterwards
+    pub fn clean_up(&mut self, config: &CleanupConfig) {
+        self.cleanups += 1;
+
+        if config.extend_range > 0.0 {
+            self.extend_dead_ends(config.extend_range);
+        }
+
+        let mut removed = vec![false; self.roads.len()];
+        if config.min_dead_end > 0 {
+            self.prune_dead_ends(config.min_dead_end as usize, &mut removed);
+        }
+        if config.min_component > 0 {
+            self.remove_components(config.min_component as usize, &mut removed);
+        }
+
+        if removed.contains(&true) {
+            let roads = self.roads
+                .drain(..)
+                .zip(removed)
+                .filter(|&(_, removed)| !removed)
+                .map(|(road, _)| road)
+                .collect();
+            self.rebuild(roads);
+        }
+    }
+
+    fn dead_ends(&self) -> Vec<NodeId> {
+        (0..self.graph.nodes().len())
+            .map(NodeId)
+            .filter(|&node| self.graph.node(node).degree() == 1)
+            .collect()
+    }
+
+    // joins dead ends onto the nearest road within range, if nothing is in the way
+    fn extend_dead_ends(&mut self, range: f64) {
+        for node in self.dead_ends() {
+            // already joined onto by an earlier one
+            if self.graph.node(node).degree() != 1 {
+                continue;
+            }
+
+            let (edge, back) = self.graph.neighbours(node).next().unwrap();
+            let end = self.graph.node(node).position();
+            let back = self.graph.node(back).position();
+            let road_type = self.roads[edge.0].road_type;
+
+            // skips roads meeting this one at its other end, joining those would only cut
+            // the corner
+            let dead_end = Road::new_with_points(road_type, back, end);
+            let (index, nearest) = match self.find_nearest_on_road(&dead_end, range) {
+                Some(found) => found,
+                None => continue,
+            };
+
+            let mut extension = Road::new_with_points(road_type, end, nearest);
+            let crossed = self.find_crossing(&extension).is_some_and(|(i, _)| i != index);
+            let blocked = self.obstacles
+                .as_ref()
+                .is_some_and(|o| !o.intervals(&end, &nearest).is_empty());
+            if crossed || blocked || !self.accept_junction_constraints(&extension, Some(index)) ||
+               !self.accept_clearance(&extension, Some(index)) ||
+               !self.accept_slope(&extension) {
+                continue;
+            }
+
+            extension.street = self.next_street;
+            self.next_street += 1;
+
+            self.split_road(index, nearest);
+            self.add_road(extension);
+        }
+    }
+
+    // removes chains of roads from dead ends back to a junction, if shorter than min roads
+    fn prune_dead_ends(&self, min: usize, removed: &mut [bool]) {
+        for start in self.dead_ends() {
+            let mut chain: Vec<EdgeId> = Vec::new();
+            let mut node = start;
+
+            let reached_junction = loop {
+                let previous = chain.last().cloned();
+                let next = self.graph
+                    .neighbours(node)
+                    .find(|&(edge, _)| Some(edge) != previous);
+
+                let (edge, next) = match next {
+                    Some(next) => next,
+                    None => break false,
+                };
+
+                chain.push(edge);
+                node = next;
+
+                let degree = self.graph.node(node).degree();
+                if degree != 2 || chain.len() >= min {
+                    break degree > 2;
+                }
+            };
+
+            // isolated lines are left to the component pass
+            if reached_junction && chain.len() < min {
+                for edge in chain {
+                    removed[edge.0] = true;
+                }
+            }
+        }
+    }
+
+    // removes connected areas of fewer than min roads
+    fn remove_components(&self, min: usize, removed: &mut [bool]) {
+        let mut visited = vec![false; self.graph.nodes().len()];
+        let mut counted = vec![false; self.roads.len()];
+
+        for start in 0..visited.len() {
+            if visited[start] {
+                continue;
+            }
+
+            let mut component: Vec<EdgeId> = Vec::new();
+            let mut stack = vec![NodeId(start)];
+            visited[start] = true;
+
+            while let Some(node) = stack.pop() {
+                for (edge, next) in self.graph.neighbours(node) {
+                    if removed[edge.0] {
+                        continue;
+                    }
+
+                    if !counted[edge.0] {
+                        counted[edge.0] = true;
+                        component.push(edge);
+                    }
+                    if !visited[next.0] {
+                        visited[next.0] = true;
+                        stack.push(next);
+                    }
+                }
+            }
+
+            if component.len() < min {
+                for edge in component {
+                    removed[edge.0] = true;
+                }
+            }
+        }
+    }
+
+    // replaces the roads, rebuilding everything indexed by them
+    fn rebuild(&mut self, roads: Vec<Road>) {
+        let mut points: Vec<Point> = roads
+            .iter()
+            .flat_map(|r| vec![r.from.unwrap(), r.to.unwrap()])
+            .collect();
+        if !points.is_empty() {
+            self.kdtree = Kdtree::new(&mut points);
+        }
+
+        self.graph = RoadGraph::new();
+        for road in &roads {
+            let (from, _) = self.graph.add_node(road.from.unwrap());
+            let (to, _) = self.graph.add_node(road.to.unwrap());
+            self.graph.add_edge(from, to, road.road_type);
+        }
+
+        self.roads = roads;
+    }
 
-        let a = road.from.unwrap().pos;
-        let b = road.to.unwrap().pos;
+    fn add_road(&mut self, mut road: Road) {
+        road.elevation = self.elevation(&road);
+        road.profile = self.config.generation_at(&road.road_type, &road.from.unwrap()).profile();
 
-        self.kdtree.insert_node(Point::from(a));
-        self.kdtree.insert_node(Point::from(b));
+        let from = self.add_node(road.from.unwrap());
+        let to = self.add_node(road.to.unwrap());
 
+        self.graph.add_edge(from, to, road.road_type);
         self.roads.push(road);
     }
 
+    fn elevation(&self, road: &Road) -> Option<(f64, f64)> {
+        self.terrain
+            .as_ref()
+            .map(|t| (t.height(&road.from.unwrap()), t.height(&road.to.unwrap())))
+    }
+
+    // welds onto an existing node at the same position if there is one
+    fn add_node(&mut self, point: Point) -> NodeId {
+        let (node, created) = self.graph.add_node(point);
+        if created {
+            self.kdtree.insert_node(point);
+        }
+
+        node
+    }
+
     // TODO check only from?
     fn is_in_range(&self, road: &Road) -> bool {
         const PADDING: f64 = 10.;
@@ -221,18 +863,23 @@ impl RoadMap {
     }
 
     // returns (accepted, merged)
-    fn accept_local_constraints(&self, road: &mut Road) -> (bool, bool) {
+    fn accept_local_constraints(&mut self, road: &mut Road) -> (bool, bool) {
         // out of range
         if !self.is_in_range(road) {
             return (false, false);
         }
 
-        let config = self.config.generation(&road.road_type());
+        if !self.accept_obstacle_constraints(road) {
+            return (false, false);
+        }
+
+        let config = self.config.generation_at(&road.road_type(), &road.from.unwrap());
 
-        // merge with nearby
+        // merge with nearby, planned roads already meet exactly
         let mut merged = false;
         let merger = road.to.unwrap();
-        if self.kdtree
+        if !road.planned &&
+           self.kdtree
                .has_neighbor_in_range(&merger, config.merge_range) {
             let nearest = self.kdtree.nearest_search(&merger);
 
@@ -253,24 +900,302 @@ impl RoadMap {
             merged = true;
         }
 
+        let planned_to = road.to.unwrap();
+        let mut snapped = None;
+        if !road.planned && !merged {
+            // onto the side of a nearby road
+            snapped = self.find_nearest_on_road(road, config.merge_range);
+            if let Some((_, at)) = snapped {
+                road.set_to(at);
+            } else if config.extend_range > 0.0 {
+                self.extend_to_reach(road, config.extend_range);
+            }
+        }
+
+        // stop at the first road crossed, creating a junction
+        let crossing = self.find_crossing(road).or(snapped);
+        if let Some((_, at)) = crossing {
+            road.set_to(at);
+        }
+
+        let crossed = crossing.map(|(i, _)| i);
+        if !road.planned &&
+           (!self.accept_junction_constraints(road, crossed) ||
+            !self.accept_clearance(road, crossed)) {
+            return (false, false);
+        }
+
+        // the end may have moved since the proposal was checked, and planned or seed
+        // roads never were
+        if !self.accept_slope(road) {
+            return (false, false);
+        }
+
+        if let Some((index, at)) = crossing {
+            // the rest of a highway carries on past the junction, before anything else
+            if road.planned && at != planned_to {
+                let mut rest = Road::new_with_points(road.road_type, at, planned_to);
+                rest.planned = true;
+                rest.street = road.street;
+                self.frontier.push(0, rest);
+            }
+
+            self.split_road(index, at);
+            merged = true;
+        }
+
         (true, merged)
     }
 
-    fn propose_with_global_goals(&self, road: &Road, branch: bool) -> Vec<Road> {
+    // rejects stubs, and roads meeting others at too sharp an angle.
+    // crossed is the road the given road will be split into, if any
+    fn accept_junction_constraints(&self, road: &Road, crossed: Option<usize>) -> bool {
+        let (from, to) = (road.from.unwrap(), road.to.unwrap());
+        let config = self.config.generation_at(&road.road_type(), &from);
+
+        if geometry::distance(&from, &to) < config.min_length {
+            return false;
+        }
+
+        let min_angle = config.min_junction_angle.to_radians();
+        if min_angle <= 0.0 {
+            return true;
+        }
+
+        let mut to_ends = self.junction_ends(&to);
+        if let Some(index) = crossed {
+            to_ends.push(self.roads[index].from.unwrap());
+            to_ends.push(self.roads[index].to.unwrap());
+        }
+
+        let heading = |at: &Point, towards: &Point| (towards.y() - at.y()).atan2(towards.x() - at.x());
+        let too_sharp = |at: &Point, along: &Point, ends: &[Point]| {
+            let angle = heading(at, along);
+            ends.iter()
+                .filter(|&end| end != at)
+                .any(|end| geometry::angle_between(angle, heading(at, end)) < min_angle)
+        };
+
+        !too_sharp(&from, &to, &self.junction_ends(&from)) && !too_sharp(&to, &from, &to_ends)
+    }
+
+    // rejects roads closer to others they don't meet than half their combined widths.
+    // crossed is the road the given road will be split into, if any
+    fn accept_clearance(&self, road: &Road, crossed: Option<usize>) -> bool {
+        let (from, to) = (road.from.unwrap(), road.to.unwrap());
+        let width = self.config.generation_at(&road.road_type(), &from).width;
+
+        // roads meeting this one, including the one it will be split into
+        self.roads
+            .iter()
+            .enumerate()
+            .filter(|&(i, _)| Some(i) != crossed)
+            .all(|(_, other)| {
+                let (a, b) = (other.from.unwrap(), other.to.unwrap());
+                let gap = (width + other.width()) / 2.0;
+                gap <= 0.0 || a == from || a == to || b == from || b == to ||
+                geometry::segment_distance(&from, &to, &a, &b) >= gap
+            })
+    }
+
+    // the far ends of the roads meeting at the point
+    fn junction_ends(&self, at: &Point) -> Vec<Point> {
+        self.graph.node_at(at).map_or_else(Vec::new, |node| {
+            self.graph
+                .neighbours(node)
+                .map(|(_, other)| self.graph.node(other).position())
+                .collect()
+        })
+    }
+
+    // cuts roads short at obstacles, or bridges them if their type allows.
+    // returns false if the road should be rejected
+    fn accept_obstacle_constraints(&self, road: &mut Road) -> bool {
+        // roads cut to less than this fraction of their length are pruned
+        const MIN_SHORTENED: f64 = 0.25;
+
+        let obstacles = match self.obstacles {
+            Some(ref o) => o,
+            None => return true,
+        };
+
+        let (from, to) = (road.from.unwrap(), road.to.unwrap());
+        let intervals = obstacles.intervals(&from, &to);
+        let shore = match intervals.first() {
+            Some(&(start, _)) => start,
+            None => return true,
+        };
+
+        let max_bridge = self.config.generation_at(&road.road_type(), &from).max_bridge_length;
+        if let Some(end) = max_bridge.and_then(|max| bridge_end(obstacles, road, &intervals, max)) {
+            road.set_to(end);
+            road.bridge = true;
+            return true;
+        }
+
+        if shore < MIN_SHORTENED {
+            return false;
+        }
+
+        road.set_to(geometry::lerp(&from, &to, shore));
+        true
+    }
+
+    // returns the road with a point closest to the end of the given road within range,
+    // and that point
+    fn find_nearest_on_road(&self, road: &Road, range: f64) -> Option<(usize, Point)> {
+        let from = road.from.unwrap();
+        let to = road.to.unwrap();
+
+        let mut nearest: Option<(usize, f64, Point)> = None;
+        for (i, other) in self.roads.iter().enumerate() {
+            let (a, b) = (other.from.unwrap(), other.to.unwrap());
+
+            // would fold back along itself
+            if a == from || b == from {
+                continue;
+            }
+
+            let point = geometry::lerp(&a, &b, geometry::closest_on_segment(&to, &a, &b));
+            let distance = geometry::distance(&to, &point);
+            if distance <= range && nearest.is_none_or(|(_, closest, _)| distance < closest) {
+                nearest = Some((i, distance, point));
+            }
+        }
+
+        nearest.map(|(i, _, point)| (i, point))
+    }
+
+    // stretches the road to meet the first road within range ahead of it, over clear ground
+    fn extend_to_reach(&self, road: &mut Road, range: f64) {
+        let (from, to) = (road.from.unwrap(), road.to.unwrap());
+        road.set_to(geometry::project(&to, road.angle(), range));
+
+        let reached = match self.find_crossing(road) {
+            Some((_, at)) => {
+                self.obstacles
+                    .as_ref()
+                    .is_none_or(|o| o.intervals(&from, &at).is_empty())
+            }
+            None => false,
+        };
+
+        if !reached {
+            road.set_to(to);
+        }
+    }
+
+    // returns the crossed road closest to the start of the given road, and the point of crossing
+    fn find_crossing(&self, road: &Road) -> Option<(usize, Point)> {
+        let from = road.from.unwrap();
+        let to = road.to.unwrap();
+
+        let mut closest: Option<(usize, f64, Point)> = None;
+        for (i, other) in self.roads.iter().enumerate() {
+            let (a, b) = (other.from.unwrap(), other.to.unwrap());
+
+            // connected roads meet rather than cross
+            if a == from || b == from || a == to || b == to {
+                continue;
+            }
+
+            if let Some((t, u)) = geometry::segment_intersection(&from, &to, &a, &b) {
+                if closest.is_none_or(|(_, closest_t, _)| t < closest_t) {
+                    // passing through an existing junction
+                    let crossing = match u {
+                        u if u <= 0.0 => a,
+                        u if u >= 1.0 => b,
+                        u => geometry::lerp(&a, &b, u),
+                    };
+                    closest = Some((i, t, crossing));
+                }
+            }
+        }
+
+        closest.map(|(i, _, crossing)| (i, crossing))
+    }
+
+    // splits an existing road into two at the given point along it
+    fn split_road(&mut self, index: usize, at: Point) {
+        let (road_type, fuel, bridge, planned, street, profile, from, to) = {
+            let road = &self.roads[index];
+            (road.road_type,
+             road.fuel,
+             road.bridge,
+             road.planned,
+             road.street,
+             road.profile,
+             road.from.unwrap(),
+             road.to.unwrap())
+        };
+
+        // already a junction
+        if at == from || at == to {
+            return;
+        }
+
+        self.roads[index].set_to(at);
+        self.roads[index].elevation = self.elevation(&self.roads[index]);
+
+        let mut second = Road::new_with_points(road_type, at, to);
+        second.set_fuel(fuel);
+        second.bridge = bridge;
+        second.planned = planned;
+        second.street = street;
+        second.profile = profile;
+        second.elevation = self.elevation(&second);
+        self.roads.push(second);
+
+        let node = self.add_node(at);
+        self.graph.split_edge(EdgeId(index), node);
+    }
+
+    // the proposal heading most nearly straight on continues the street, if not turning
+    // too far, the rest start new streets
+    fn continue_street(&mut self, parent: &Road, proposed: &mut [Road]) {
+        let angle = parent.angle();
+        let straight = proposed
+            .iter()
+            .enumerate()
+            .filter(|&(_, r)| r.road_type == parent.road_type)
+            .map(|(i, r)| (i, geometry::angle_between(angle, r.angle())))
+            .filter(|&(_, turn)| turn < FRAC_PI_4)
+            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+            .map(|(i, _)| i);
+
+        for (i, road) in proposed.iter_mut().enumerate() {
+            if Some(i) == straight {
+                road.street = parent.street;
+            } else {
+                road.street = self.next_street;
+                self.next_street += 1;
+            }
+        }
+    }
+
+    fn propose_with_global_goals(&mut self, road: &Road, branch: bool) -> Vec<Road> {
 
         let mut vec: Vec<Road> = Vec::new();
 
-        rules::propose_roads(self.config.generation(&road.road_type()),
+        rules::propose_roads(&self.config,
                              road,
                              branch,
+                             &mut self.rng,
                              &mut vec);
 
-        let range = self.config.generation(&road.road_type()).fuel_range;
+        // the rest of a highway is already planned
+        if road.planned {
+            vec.retain(|r| r.road_type() != road.road_type());
+        }
+
+        self.apply_density_goals(road, &mut vec);
+        self.apply_slope_limits(&mut vec);
+
+        let range = self.config.generation_at(&road.road_type(), &road.to.unwrap()).fuel_range;
 
         if branch {
-            let mut rng = thread_rng();
             for r in &mut vec {
-                let fuel = rng.gen_range(range[0], range[1]);
+                let fuel = self.rng.gen_range(range[0], range[1]);
                 r.set_fuel(fuel);
             }
         } else {
@@ -281,4 +1206,208 @@ impl RoadMap {
 
         vec
     }
+
+    // steers proposals towards dense areas and prunes branches in empty ones
+    fn apply_density_goals(&self, parent: &Road, proposed: &mut Vec<Road>) {
+        let density = match self.density {
+            Some(ref d) => d,
+            None => return,
+        };
+
+        let default_goals = DensityConfig::default();
+        let goals = self.config.density.as_ref().unwrap_or(&default_goals);
+        let parent_angle = parent.angle();
+
+        proposed.retain(|r| {
+            let is_branch = geometry::angle_between(r.angle(), parent_angle) > FRAC_PI_4;
+            let end = r.to.unwrap();
+            !is_branch || density.sample(end.x(), end.y()) >= goals.branch_threshold
+        });
+
+        const DEVIATIONS: [f64; 4] = [-1.0, -0.5, 0.5, 1.0];
+
+        for road in proposed.iter_mut() {
+            let from = road.from.unwrap();
+            let length = road.length();
+            let max_deviation = self.config
+                .generation_at(&road.road_type(), &from)
+                .density_angle
+                .to_radians();
+
+            // prefer the original angle unless another is denser
+            let to = road.to.unwrap();
+            let mut best = (road.angle(), density.sample(to.x(), to.y()));
+            if max_deviation > 0.0 {
+                let angle = best.0;
+                for deviation in &DEVIATIONS {
+                    let candidate = angle + deviation * max_deviation;
+                    let end = geometry::project(&from, candidate, length);
+                    let value = density.sample(end.x(), end.y());
+                    if value > best.1 {
+                        best = (candidate, value);
+                    }
+                }
+            }
+
+            let (angle, value) = best;
+            let scale = goals.length_scale[0] + (goals.length_scale[1] - goals.length_scale[0]) * value;
+            road.redirect(angle, length * scale);
+        }
+    }
+
+    // rejects roads steeper than their type allows, bridges only between their ends
+    fn accept_slope(&self, road: &Road) -> bool {
+        let terrain = match self.terrain {
+            Some(ref t) => t,
+            None => return true,
+        };
+
+        let (from, to) = (road.from.unwrap(), road.to.unwrap());
+        let max_slope = match self.config.generation_at(&road.road_type(), &from).max_slope {
+            Some(max) => max,
+            None => return true,
+        };
+
+        if road.bridge {
+            // the deck runs straight between the banks
+            (terrain.height(&to) - terrain.height(&from)).abs() <= max_slope * road.length()
+        } else {
+            terrain.slope(&from, &to) <= max_slope
+        }
+    }
+
+    // turns proposals that are too steep, dropping any that can't be made shallow enough
+    fn apply_slope_limits(&self, proposed: &mut Vec<Road>) {
+        // radians to try turning by, in order
+        const TURNS: [f64; 6] = [0.175, -0.175, 0.35, -0.35, 0.524, -0.524];
+
+        let terrain = match self.terrain {
+            Some(ref t) => t,
+            None => return,
+        };
+
+        proposed.retain_mut(|road| {
+            let from = road.from.unwrap();
+            let max_slope = match self.config.generation_at(&road.road_type(), &from).max_slope {
+                Some(max) => max,
+                None => return true,
+            };
+
+            if terrain.slope(&from, &road.to.unwrap()) <= max_slope {
+                return true;
+            }
+
+            let (angle, length) = (road.angle(), road.length());
+            let turn = TURNS
+                .iter()
+                .find(|&turn| {
+                          let to = geometry::project(&from, angle + turn, length);
+                          terrain.slope(&from, &to) <= max_slope
+                      });
+
+            match turn {
+                Some(turn) => {
+                    road.redirect(angle + turn, length);
+                    true
+                }
+                None => false,
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use toml;
+
+    const CONFIG: &str = r#"
+        [window]
+        width = 300
+        height = 200
+
+        [[classes]]
+        name = "large"
+        child = "small"
+
+        [classes.generation]
+        fuel_range = [2, 5]
+        road_chance = 0.8
+        road_length = 20.0
+        merge_range = 18.0
+        organic_angle = 15
+        child_chance = 0.2
+
+        [[classes]]
+        name = "small"
+
+        [classes.generation]
+        fuel_range = [1, 2]
+        road_chance = 0.5
+        road_length = 8.0
+        merge_range = 6.0
+        organic_angle = 15
+        child_chance = 0.3
+
+        [density.field]
+        source = "noise"
+        scale = 100.0
+        octaves = 2
+
+        [frontier]
+        centres = 2
+        min_spacing = 80.0
+    "#;
+
+    fn config() -> Config {
+        let mut config: Config = toml::from_str(CONFIG).unwrap();
+        config.resolve_classes().unwrap();
+        config
+    }
+
+    fn generate(seed: u64) -> Vec<(RoadType, Point, Point)> {
+        let mut roadmap = RoadMap::with_seed(config(), seed).unwrap();
+        while !roadmap.is_finished() {
+            roadmap.advance().unwrap();
+        }
+
+        roadmap
+            .roads()
+            .iter()
+            .map(|road| (road.road_type(), road.from.unwrap(), road.to.unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn same_seed_same_roads() {
+        let roads = generate(42);
+        assert!(!roads.is_empty());
+        assert_eq!(roads, generate(42));
+    }
+
+    #[test]
+    fn cleans_up_when_growth_finishes_after_cleaning_by_hand() {
+        let cleanup = CleanupConfig {
+            min_dead_end: 2,
+            extend_range: 5.0,
+            min_component: 5,
+        };
+
+        let mut config = config();
+        config.window.growth_increment = Some(10);
+        config.cleanup = Some(cleanup.clone());
+
+        let mut roadmap = RoadMap::with_seed(config, 42).unwrap();
+        roadmap.advance().unwrap();
+        assert!(!roadmap.is_finished());
+
+        roadmap.clean_up(&cleanup);
+        while !roadmap.is_finished() {
+            roadmap.advance().unwrap();
+        }
+        assert_eq!(roadmap.cleanups(), 2);
+
+        roadmap.advance().unwrap();
+        assert_eq!(roadmap.cleanups(), 2);
+    }
 }
@@ -0,0 +1,99 @@
+use Point;
+use std::f64::consts::PI;
+
+// tolerance for treating segments as touching at their ends
+const EPSILON: f64 = 1e-9;
+
+// returns the parameters (t, u) along a->b and c->d at which they cross, if they do
+pub fn segment_intersection(a: &Point, b: &Point, c: &Point, d: &Point) -> Option<(f64, f64)> {
+    let r = [b.x() - a.x(), b.y() - a.y()];
+    let s = [d.x() - c.x(), d.y() - c.y()];
+
+    let denom = cross(r, s);
+    if denom.abs() < EPSILON {
+        // parallel or collinear
+        return None;
+    }
+
+    let ac = [c.x() - a.x(), c.y() - a.y()];
+    let t = cross(ac, s) / denom;
+    let u = cross(ac, r) / denom;
+
+    if (0.0..=1.0).contains(&t) && (0.0..=1.0).contains(&u) {
+        Some((t, u))
+    } else {
+        None
+    }
+}
+
+// point at parameter t along a->b
+pub fn lerp(a: &Point, b: &Point, t: f64) -> Point {
+    Point::new(a.x() + (b.x() - a.x()) * t, a.y() + (b.y() - a.y()) * t)
+}
+
+pub fn distance(a: &Point, b: &Point) -> f64 {
+    (b.x() - a.x()).hypot(b.y() - a.y())
+}
+
+// parameter along a->b of the point on it closest to p
+pub fn closest_on_segment(p: &Point, a: &Point, b: &Point) -> f64 {
+    let ab = [b.x() - a.x(), b.y() - a.y()];
+    let length_squared = ab[0] * ab[0] + ab[1] * ab[1];
+    if length_squared < EPSILON {
+        return 0.0;
+    }
+
+    let t = ((p.x() - a.x()) * ab[0] + (p.y() - a.y()) * ab[1]) / length_squared;
+    t.clamp(0.0, 1.0)
+}
+
+// shortest distance between a->b and c->d
+pub fn segment_distance(a: &Point, b: &Point, c: &Point, d: &Point) -> f64 {
+    if segment_intersection(a, b, c, d).is_some() {
+        return 0.0;
+    }
+
+    let to_segment = |p: &Point, from: &Point, to: &Point| {
+        distance(p, &lerp(from, to, closest_on_segment(p, from, to)))
+    };
+
+    to_segment(a, c, d)
+        .min(to_segment(b, c, d))
+        .min(to_segment(c, a, b))
+        .min(to_segment(d, a, b))
+}
+
+// end of a segment of the given angle and length
+pub fn project(from: &Point, angle: f64, length: f64) -> Point {
+    Point::new(from.x() + angle.cos() * length,
+               from.y() + angle.sin() * length)
+}
+
+// smallest absolute difference between two angles in radians
+pub fn angle_between(a: f64, b: f64) -> f64 {
+    let diff = (a - b) % (2.0 * PI);
+    let diff = diff.abs();
+    diff.min(2.0 * PI - diff)
+}
+
+// even-odd rule
+pub fn point_in_polygon(point: &Point, polygon: &[Point]) -> bool {
+    let mut inside = false;
+    let mut j = polygon.len().wrapping_sub(1);
+
+    for (i, a) in polygon.iter().enumerate() {
+        let b = &polygon[j];
+        if (a.y() > point.y()) != (b.y() > point.y()) &&
+           point.x() < (b.x() - a.x()) * (point.y() - a.y()) / (b.y() - a.y()) + a.x() {
+            inside = !inside;
+        }
+        j = i;
+    }
+
+    inside
+}
+
+#[inline]
+fn cross(a: [f64; 2], b: [f64; 2]) -> f64 {
+    a[0] * b[1] - a[1] * b[0]
+}
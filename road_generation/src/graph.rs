@@ -0,0 +1,217 @@
+use {Point, RoadType};
+use std::collections::HashMap;
+
+// stable while roads are added, but renumbered by RoadMap::clean_up
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeId(pub usize);
+
+// indexes into both RoadGraph::edges() and RoadMap::roads(). stable while roads are
+// added, but renumbered by RoadMap::clean_up, see RoadMap::cleanups()
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct EdgeId(pub usize);
+
+#[derive(Debug)]
+pub struct Node {
+    position: Point,
+    edges: Vec<EdgeId>,
+}
+
+#[derive(Debug)]
+pub struct Edge {
+    from: NodeId,
+    to: NodeId,
+    road_type: RoadType,
+}
+
+// a road leaving a node
+#[derive(Debug, Copy, Clone)]
+pub struct Exit {
+    pub edge: EdgeId,
+    // the node at the other end
+    pub to: NodeId,
+    // radians
+    pub angle: f64,
+}
+
+// road network with endpoints welded into shared nodes
+#[derive(Debug, Default)]
+pub struct RoadGraph {
+    nodes: Vec<Node>,
+    edges: Vec<Edge>,
+
+    // exact positions only, merging has already snapped roads onto existing points
+    lookup: HashMap<[u64; 2], NodeId>,
+}
+
+impl Node {
+    pub fn position(&self) -> Point {
+        self.position
+    }
+
+    pub fn edges(&self) -> &[EdgeId] {
+        &self.edges
+    }
+
+    pub fn degree(&self) -> usize {
+        self.edges.len()
+    }
+}
+
+impl Edge {
+    pub fn from(&self) -> NodeId {
+        self.from
+    }
+
+    pub fn to(&self) -> NodeId {
+        self.to
+    }
+
+    pub fn nodes(&self) -> (NodeId, NodeId) {
+        (self.from, self.to)
+    }
+
+    pub fn road_type(&self) -> RoadType {
+        self.road_type
+    }
+
+    // the node at the other end of this edge
+    pub fn opposite(&self, node: NodeId) -> NodeId {
+        if node == self.from { self.to } else { self.from }
+    }
+}
+
+// exact position, for looking up points that are the same down to the bit
+pub(crate) fn key(point: &Point) -> [u64; 2] {
+    [point.x().to_bits(), point.y().to_bits()]
+}
+
+// roads from the given position to each (edge, node at the other end, its position),
+// in order of the angle they leave at
+pub(crate) fn sorted_exits<I>(from: &Point, roads: I) -> Vec<Exit>
+    where I: Iterator<Item = (EdgeId, NodeId, Point)>
+{
+    let mut exits: Vec<Exit> = roads
+        .map(|(edge, to, other)| {
+                 Exit {
+                     edge,
+                     to,
+                     angle: (other.y() - from.y()).atan2(other.x() - from.x()),
+                 }
+             })
+        .collect();
+
+    exits.sort_by(|a, b| a.angle.partial_cmp(&b.angle).unwrap());
+    exits
+}
+
+impl RoadGraph {
+    pub fn new() -> RoadGraph {
+        RoadGraph::default()
+    }
+
+    pub fn nodes(&self) -> &[Node] {
+        &self.nodes
+    }
+
+    pub fn edges(&self) -> &[Edge] {
+        &self.edges
+    }
+
+    pub fn node(&self, id: NodeId) -> &Node {
+        &self.nodes[id.0]
+    }
+
+    pub fn edge(&self, id: EdgeId) -> &Edge {
+        &self.edges[id.0]
+    }
+
+    pub fn node_at(&self, point: &Point) -> Option<NodeId> {
+        self.lookup.get(&key(point)).cloned()
+    }
+
+    // (edge, node at the other end) for every edge touching the given node
+    pub fn neighbours<'a>(&'a self, node: NodeId) -> impl Iterator<Item = (EdgeId, NodeId)> + 'a {
+        self.node(node)
+            .edges
+            .iter()
+            .map(move |&e| (e, self.edge(e).opposite(node)))
+    }
+
+    // roads leaving the node, in order of the angle they leave at
+    pub fn exits(&self, node: NodeId) -> Vec<Exit> {
+        let neighbours = self.neighbours(node).map(|(edge, to)| (edge, to, self.node(to).position));
+        sorted_exits(&self.node(node).position, neighbours)
+    }
+
+    // for each node, the first node within the given distance of it, which may be itself
+    pub fn weld_nodes(&self, tolerance: f64) -> Vec<NodeId> {
+        let cell_of = |p: &Point| ((p.x() / tolerance).floor() as i64, (p.y() / tolerance).floor() as i64);
+        let mut cells: HashMap<(i64, i64), Vec<NodeId>> = HashMap::new();
+        let mut welded = Vec::with_capacity(self.nodes.len());
+
+        for (i, node) in self.nodes.iter().enumerate() {
+            let (cx, cy) = cell_of(&node.position);
+            let existing = (-1..2)
+                .flat_map(|dx| (-1..2).map(move |dy| (cx + dx, cy + dy)))
+                .filter_map(|cell| cells.get(&cell))
+                .flat_map(|ids| ids.iter())
+                .filter(|id| {
+                    let other = self.nodes[id.0].position;
+                    (other.x() - node.position.x()).hypot(other.y() - node.position.y()) <= tolerance
+                })
+                .min()
+                .cloned();
+
+            welded.push(existing.unwrap_or_else(|| {
+                cells.entry((cx, cy)).or_default().push(NodeId(i));
+                NodeId(i)
+            }));
+        }
+
+        welded
+    }
+
+    // returns the node and whether it was newly created
+    pub(crate) fn add_node(&mut self, position: Point) -> (NodeId, bool) {
+        if let Some(id) = self.node_at(&position) {
+            return (id, false);
+        }
+
+        let id = NodeId(self.nodes.len());
+        self.nodes.push(Node {
+                            position,
+                            edges: Vec::new(),
+                        });
+        self.lookup.insert(key(&position), id);
+        (id, true)
+    }
+
+    pub(crate) fn add_edge(&mut self, from: NodeId, to: NodeId, road_type: RoadType) -> EdgeId {
+        let id = EdgeId(self.edges.len());
+        self.edges.push(Edge {
+                            from,
+                            to,
+                            road_type,
+                        });
+
+        self.nodes[from.0].edges.push(id);
+        self.nodes[to.0].edges.push(id);
+        id
+    }
+
+    // shortens the edge to end at the given node, adding a new edge for the remainder.
+    // the original edge keeps its id
+    pub(crate) fn split_edge(&mut self, edge: EdgeId, at: NodeId) -> EdgeId {
+        let (old_to, road_type) = {
+            let e = &mut self.edges[edge.0];
+            let old_to = e.to;
+            e.to = at;
+            (old_to, e.road_type)
+        };
+
+        self.nodes[old_to.0].edges.retain(|&e| e != edge);
+        self.nodes[at.0].edges.push(edge);
+
+        self.add_edge(at, old_to, road_type)
+    }
+}
@@ -0,0 +1,232 @@
+use Point;
+use config::HighwayConfig;
+use obstacles::ObstacleMap;
+use geometry;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::f64::consts::SQRT_2;
+
+// no previous cell on the path
+const NONE: usize = usize::MAX;
+
+// routes between centres, each from the first centre to the second, avoiding obstacles
+pub fn plan(centres: &[Point],
+            config: &HighwayConfig,
+            obstacles: Option<&ObstacleMap>,
+            width: u32,
+            height: u32)
+            -> Vec<Vec<Point>> {
+    links(centres, config.extra_links)
+        .into_iter()
+        .filter_map(|(a, b)| {
+                        route(&centres[a], &centres[b], obstacles, config.cell_size, width, height)
+                    })
+        .collect()
+}
+
+// pairs of centres to link: a minimum spanning tree, then the shortest of the remaining pairs
+pub fn links(centres: &[Point], extra: u32) -> Vec<(usize, usize)> {
+    let n = centres.len();
+    let mut links: Vec<(usize, usize)> = Vec::new();
+    if n < 2 {
+        return links;
+    }
+
+    let length = |&(a, b): &(usize, usize)| geometry::distance(&centres[a], &centres[b]);
+
+    // prim's, growing from the first centre
+    let mut in_tree = vec![false; n];
+    let mut closest: Vec<(usize, usize)> = (0..n).map(|i| (0, i)).collect();
+    in_tree[0] = true;
+
+    for _ in 1..n {
+        let next = (0..n)
+            .filter(|&i| !in_tree[i])
+            .min_by(|&a, &b| length(&closest[a]).partial_cmp(&length(&closest[b])).unwrap())
+            .unwrap();
+
+        in_tree[next] = true;
+        links.push(closest[next]);
+
+        for i in 0..n {
+            if !in_tree[i] && length(&(next, i)) < length(&closest[i]) {
+                closest[i] = (next, i);
+            }
+        }
+    }
+
+    let mut rest: Vec<(usize, usize)> = (0..n)
+        .flat_map(|a| (a + 1..n).map(move |b| (a, b)))
+        .filter(|&(a, b)| !links.iter().any(|&l| l == (a, b) || l == (b, a)))
+        .collect();
+    rest.sort_by(|a, b| length(a).partial_cmp(&length(b)).unwrap());
+
+    links.extend(rest.into_iter().take(extra as usize));
+    links
+}
+
+// a path around obstacles on a grid of the given cell size, straightened where possible.
+// none if the centres are cut off from each other
+pub fn route(from: &Point,
+             to: &Point,
+             obstacles: Option<&ObstacleMap>,
+             cell_size: f64,
+             width: u32,
+             height: u32)
+             -> Option<Vec<Point>> {
+    let obstacles = match obstacles {
+        Some(o) if !o.intervals(from, to).is_empty() => o,
+        _ => return Some(vec![*from, *to]),
+    };
+
+    let grid = Grid::new(obstacles, cell_size, width, height);
+    let cells = grid.search(grid.cell_at(from), grid.cell_at(to))?;
+
+    // the cells at the ends are only near the centres
+    let mut path = vec![*from];
+    if cells.len() > 2 {
+        path.extend(cells[1..cells.len() - 1].iter().map(|&c| grid.position(c)));
+    }
+    path.push(*to);
+
+    Some(straighten(&path, obstacles))
+}
+
+// skips every point that can be seen past
+fn straighten(path: &[Point], obstacles: &ObstacleMap) -> Vec<Point> {
+    let mut straightened = vec![path[0]];
+
+    let mut i = 0;
+    while i < path.len() - 1 {
+        i = (i + 2..path.len())
+            .rev()
+            .find(|&j| obstacles.intervals(&path[i], &path[j]).is_empty())
+            .unwrap_or(i + 1);
+        straightened.push(path[i]);
+    }
+
+    straightened
+}
+
+struct Grid {
+    cell_size: f64,
+    columns: usize,
+    rows: usize,
+    blocked: Vec<bool>,
+}
+
+// a cell waiting to be searched, lowest estimated cost first
+struct Open {
+    estimate: f64,
+    cell: usize,
+}
+
+impl Grid {
+    fn new(obstacles: &ObstacleMap, cell_size: f64, width: u32, height: u32) -> Grid {
+        let columns = (width as f64 / cell_size).ceil() as usize + 1;
+        let rows = (height as f64 / cell_size).ceil() as usize + 1;
+
+        let mut grid = Grid {
+            cell_size,
+            columns,
+            rows,
+            blocked: Vec::with_capacity(columns * rows),
+        };
+
+        grid.blocked = (0..columns * rows)
+            .map(|c| obstacles.contains(&grid.position(c)))
+            .collect();
+        grid
+    }
+
+    fn position(&self, cell: usize) -> Point {
+        Point::new((cell % self.columns) as f64 * self.cell_size,
+                   (cell / self.columns) as f64 * self.cell_size)
+    }
+
+    fn cell_at(&self, point: &Point) -> usize {
+        let column = (point.x() / self.cell_size).round().max(0.0) as usize;
+        let row = (point.y() / self.cell_size).round().max(0.0) as usize;
+        row.min(self.rows - 1) * self.columns + column.min(self.columns - 1)
+    }
+
+    // a*, returning the cells along the way from start to goal inclusive
+    fn search(&self, start: usize, goal: usize) -> Option<Vec<usize>> {
+        let goal_position = self.position(goal);
+        let heuristic = |cell: usize| geometry::distance(&self.position(cell), &goal_position);
+
+        let mut cost = vec![f64::MAX; self.blocked.len()];
+        let mut previous = vec![NONE; self.blocked.len()];
+        let mut open = BinaryHeap::new();
+
+        cost[start] = 0.0;
+        open.push(Open {
+                      estimate: heuristic(start),
+                      cell: start,
+                  });
+
+        while let Some(Open { estimate, cell }) = open.pop() {
+            if cell == goal {
+                let mut path = vec![goal];
+                while previous[*path.last().unwrap()] != NONE {
+                    path.push(previous[*path.last().unwrap()]);
+                }
+                path.reverse();
+                return Some(path);
+            }
+
+            // already reached more cheaply
+            if estimate > cost[cell] + heuristic(cell) {
+                continue;
+            }
+
+            let (column, row) = ((cell % self.columns) as isize, (cell / self.columns) as isize);
+            for &(dx, dy) in &[(1, 0), (-1, 0), (0, 1), (0, -1), (1, 1), (1, -1), (-1, 1), (-1, -1)] {
+                let (x, y) = (column + dx, row + dy);
+                if x < 0 || y < 0 || x >= self.columns as isize || y >= self.rows as isize {
+                    continue;
+                }
+
+                // the centres themselves may be just inside an obstacle
+                let next = y as usize * self.columns + x as usize;
+                if self.blocked[next] && next != goal {
+                    continue;
+                }
+
+                let step = if dx != 0 && dy != 0 { SQRT_2 } else { 1.0 };
+                let next_cost = cost[cell] + step * self.cell_size;
+                if next_cost < cost[next] {
+                    cost[next] = next_cost;
+                    previous[next] = cell;
+                    open.push(Open {
+                                  estimate: next_cost + heuristic(next),
+                                  cell: next,
+                              });
+                }
+            }
+        }
+
+        None
+    }
+}
+
+impl PartialEq for Open {
+    fn eq(&self, other: &Open) -> bool {
+        self.estimate == other.estimate
+    }
+}
+
+impl Eq for Open {}
+
+impl PartialOrd for Open {
+    fn partial_cmp(&self, other: &Open) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Open {
+    // reversed, BinaryHeap is a max-heap
+    fn cmp(&self, other: &Open) -> Ordering {
+        other.estimate.partial_cmp(&self.estimate).unwrap_or(Ordering::Equal)
+    }
+}
@@ -5,30 +5,74 @@ extern crate smallvec;
 
 #[macro_use]
 extern crate serde_derive;
+#[macro_use]
+extern crate serde_json;
+extern crate bincode;
 extern crate toml;
 
 pub mod generator;
 pub mod config;
+pub mod graph;
+pub mod field;
+pub mod obstacles;
+pub mod terrain;
+pub mod blocks;
+pub mod lots;
+pub mod buildings;
+pub mod highways;
+pub mod streets;
+pub mod mesh;
+pub mod save;
+pub mod export;
 mod rules;
+mod geometry;
+mod frontier;
 
 use kdtree::kdtree::Kdtree;
-use std::collections::VecDeque;
+use rand::XorShiftRng;
+use frontier::Frontier;
+use field::ScalarField;
+use obstacles::ObstacleMap;
+use terrain::Heightmap;
 pub use config::Config;
+pub use graph::{RoadGraph, NodeId, EdgeId};
+pub use rules::GenerationRule;
 
 #[derive(Debug)]
 pub enum RoadError {
     Args(String),
     Settings(String),
     Unknown(&'static str),
+    // reading or writing a map file
+    Map(String),
 }
 
 
 pub struct RoadMap {
     kdtree: Kdtree<Point>,
     roads: Vec<Road>,
-    frontier: VecDeque<Road>,
+    graph: RoadGraph,
+    frontier: Frontier,
 
     config: config::Config,
+    seed: u64,
+    rng: XorShiftRng,
+
+    density: Option<Box<dyn ScalarField>>,
+    obstacles: Option<ObstacleMap>,
+    terrain: Option<Heightmap>,
+
+    // where the initial roads started
+    centres: Vec<Point>,
+
+    // id for the next road not continuing a street
+    next_street: usize,
+
+    // times the roads have been cleaned up and renumbered
+    cleanups: u32,
+
+    // the cleanup once growth finishes has been run
+    cleaned: bool,
 }
 
 #[derive(Debug, Copy, Clone, PartialEq)]
@@ -42,11 +86,40 @@ pub struct Road {
     to: Option<Point>,
     road_type: RoadType,
     fuel: u32,
+    bridge: bool,
+    elevation: Option<(f64, f64)>,
+
+    // laid out ahead of time, so only branches off
+    planned: bool,
+
+    // shared with the road this one continues on from
+    street: usize,
+
+    profile: RoadProfile,
+}
+
+// index of a road class in the config, the first being the most important
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct RoadType(pub usize);
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Surface {
+    #[default]
+    Asphalt,
+    Concrete,
+    Cobbles,
+    Gravel,
+    Dirt,
 }
 
-#[derive(Debug, Copy, Clone)]
-pub enum RoadType {
-    Small = 0,
-    Medium,
-    Large,
+// the physical road, from the config of its type where it was built
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct RoadProfile {
+    // total across all lanes
+    pub width: f64,
+    pub lanes: u32,
+    // km/h
+    pub speed_limit: f64,
+    pub surface: Surface,
 }
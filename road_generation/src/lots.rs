@@ -0,0 +1,230 @@
+use {Point, EdgeId};
+use blocks::{self, Block};
+use config::LotConfig;
+use geometry;
+
+// deepest recursion when splitting a single block
+const MAX_DEPTH: u32 = 24;
+
+// a plot of land within a block, with frontage onto at least one road
+#[derive(Debug, Clone)]
+pub struct Lot {
+    polygon: Vec<Point>,
+    sides: Vec<Option<EdgeId>>,
+    area: f64,
+    road: EdgeId,
+    frontage: (Point, Point),
+    frontage_width: f64,
+}
+
+impl Lot {
+    // not repeating the first point at the end
+    pub fn polygon(&self) -> &[Point] {
+        &self.polygon
+    }
+
+    // the road along each side if any, from each point of the polygon to the next
+    pub fn sides(&self) -> &[Option<EdgeId>] {
+        &self.sides
+    }
+
+    pub fn area(&self) -> f64 {
+        self.area
+    }
+
+    // the road with the most frontage
+    pub fn road(&self) -> EdgeId {
+        self.road
+    }
+
+    // the longest side along the road
+    pub fn frontage(&self) -> (Point, Point) {
+        self.frontage
+    }
+
+    // total length of the sides along the road
+    pub fn frontage_width(&self) -> f64 {
+        self.frontage_width
+    }
+}
+
+// point and the road along the side starting at it, if any
+pub(crate) type Side = (Point, Option<EdgeId>);
+
+// recursively splits the block across its oriented bounding box until lots are small enough
+pub fn subdivide(block: &Block, config: &LotConfig) -> Vec<Lot> {
+    let polygon: Vec<Side> = block.polygon()
+        .iter()
+        .zip(block.sides())
+        .map(|(&p, &road)| (p, Some(road)))
+        .collect();
+
+    let mut lots = Vec::new();
+    split(polygon, config, 0, &mut lots);
+    lots
+}
+
+fn split(polygon: Vec<Side>, config: &LotConfig, depth: u32, out: &mut Vec<Lot>) {
+    let points: Vec<Point> = polygon.iter().map(|&(p, _)| p).collect();
+    let area = blocks::signed_area(&points);
+    if area < config.min_area {
+        return;
+    }
+
+    if area > config.max_area && depth < MAX_DEPTH {
+        // across the longest axis first
+        let (centre, long, short) = oriented_bounds(&points);
+        for axis in &[long, short] {
+            let (a, b) = clip(&polygon, &centre, axis);
+            if is_viable(&a, config) && is_viable(&b, config) {
+                split(a, config, depth + 1, out);
+                split(b, config, depth + 1, out);
+                return;
+            }
+        }
+    }
+
+    // can't be split any further without cutting a lot off from the road
+    if let Some(lot) = to_lot(polygon, area, config) {
+        out.push(lot);
+    }
+}
+
+fn to_lot(polygon: Vec<Side>, area: f64, config: &LotConfig) -> Option<Lot> {
+    let (road, width) = best_frontage(&polygon)?;
+    if width < config.min_frontage {
+        return None;
+    }
+
+    let n = polygon.len();
+    let frontage = (0..n)
+        .filter(|&i| polygon[i].1 == Some(road))
+        .map(|i| (polygon[i].0, polygon[(i + 1) % n].0))
+        .max_by(|a, b| {
+                    let a = geometry::distance(&a.0, &a.1);
+                    let b = geometry::distance(&b.0, &b.1);
+                    a.partial_cmp(&b).unwrap()
+                })
+        .unwrap();
+
+    let (polygon, sides) = polygon.into_iter().unzip();
+    Some(Lot {
+             polygon,
+             sides,
+             area,
+             road,
+             frontage,
+             frontage_width: width,
+         })
+}
+
+// big enough and facing a road
+fn is_viable(polygon: &[Side], config: &LotConfig) -> bool {
+    if polygon.len() < 3 {
+        return false;
+    }
+
+    let points: Vec<Point> = polygon.iter().map(|&(p, _)| p).collect();
+    blocks::signed_area(&points) >= config.min_area &&
+    best_frontage(polygon).is_some_and(|(_, width)| width >= config.min_frontage)
+}
+
+// the road with the longest total length of sides along it
+fn best_frontage(polygon: &[Side]) -> Option<(EdgeId, f64)> {
+    let n = polygon.len();
+    let mut widths: Vec<(EdgeId, f64)> = Vec::new();
+
+    for i in 0..n {
+        if let (p, Some(road)) = polygon[i] {
+            let length = geometry::distance(&p, &polygon[(i + 1) % n].0);
+            match widths.iter_mut().find(|&&mut (r, _)| r == road) {
+                Some(entry) => entry.1 += length,
+                None => widths.push((road, length)),
+            }
+        }
+    }
+
+    widths
+        .into_iter()
+        .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+}
+
+// (centre, long axis, short axis) of the smallest rectangle aligned to a side of the polygon
+pub(crate) fn oriented_bounds(points: &[Point]) -> (Point, [f64; 2], [f64; 2]) {
+    let n = points.len();
+    let mut best: Option<(f64, Point, [f64; 2], [f64; 2])> = None;
+
+    for i in 0..n {
+        let (a, b) = (&points[i], &points[(i + 1) % n]);
+        let length = geometry::distance(a, b);
+        if length <= 0.0 {
+            continue;
+        }
+
+        let u = [(b.x() - a.x()) / length, (b.y() - a.y()) / length];
+        let v = [-u[1], u[0]];
+
+        let (mut min_u, mut max_u, mut min_v, mut max_v) = (f64::MAX, f64::MIN, f64::MAX, f64::MIN);
+        for p in points {
+            let (pu, pv) = (dot(p, u), dot(p, v));
+            min_u = min_u.min(pu);
+            max_u = max_u.max(pu);
+            min_v = min_v.min(pv);
+            max_v = max_v.max(pv);
+        }
+
+        let area = (max_u - min_u) * (max_v - min_v);
+        if best.is_none_or(|(best_area, ..)| area < best_area) {
+            let (mid_u, mid_v) = ((min_u + max_u) / 2.0, (min_v + max_v) / 2.0);
+            let centre = Point::new(u[0] * mid_u + v[0] * mid_v, u[1] * mid_u + v[1] * mid_v);
+
+            best = Some(if max_u - min_u >= max_v - min_v {
+                            (area, centre, u, v)
+                        } else {
+                            (area, centre, v, u)
+                        });
+        }
+    }
+
+    best.map_or((points[0], [1.0, 0.0], [0.0, 1.0]),
+                |(_, centre, long, short)| (centre, long, short))
+}
+
+// splits the polygon by the line through the point with the given normal.
+// sides along the cut have no road
+fn clip(polygon: &[Side], point: &Point, normal: &[f64; 2]) -> (Vec<Side>, Vec<Side>) {
+    let offset = dot(point, *normal);
+    let front = clip_half(polygon, |p| dot(p, *normal) - offset);
+    let back = clip_half(polygon, |p| offset - dot(p, *normal));
+    (front, back)
+}
+
+// keeps the part of the polygon where the distance function is positive
+pub(crate) fn clip_half<F: Fn(&Point) -> f64>(polygon: &[Side], distance: F) -> Vec<Side> {
+    let n = polygon.len();
+    let mut out = Vec::with_capacity(n + 2);
+
+    for i in 0..n {
+        let (cur, road) = polygon[i];
+        let next = polygon[(i + 1) % n].0;
+        let (d_cur, d_next) = (distance(&cur), distance(&next));
+
+        let crossing = || geometry::lerp(&cur, &next, d_cur / (d_cur - d_next));
+        match (d_cur >= 0.0, d_next >= 0.0) {
+            (true, true) => out.push((cur, road)),
+            (true, false) => {
+                out.push((cur, road));
+                out.push((crossing(), None));
+            }
+            (false, true) => out.push((crossing(), road)),
+            (false, false) => {}
+        }
+    }
+
+    out
+}
+
+#[inline]
+fn dot(p: &Point, v: [f64; 2]) -> f64 {
+    p.x() * v[0] + p.y() * v[1]
+}
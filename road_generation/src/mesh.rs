@@ -0,0 +1,179 @@
+use {Point, Road, RoadGraph, EdgeId, NodeId};
+use graph::Exit;
+use geometry;
+
+// roads narrower than this, including those without a width, are built this wide
+const MIN_WIDTH: f64 = 1.0;
+
+// junction angles are treated as at least this, radians, so sharp ones aren't trimmed forever
+const MIN_ANGLE: f64 = 0.2;
+
+// most of a road that can be trimmed away at each end
+const MAX_TRIM: f64 = 0.45;
+
+// triangles with less area than this are dropped, such as the joins between straight roads
+const MIN_AREA: f64 = 1e-9;
+
+// road surfaces as triangles with y up, so the map's y becomes z.
+// front faces point up
+#[derive(Debug, Clone, Default)]
+pub struct Mesh {
+    positions: Vec<[f32; 3]>,
+    uvs: Vec<[f32; 2]>,
+    indices: Vec<u32>,
+}
+
+impl Mesh {
+    // x, height, y
+    pub fn positions(&self) -> &[[f32; 3]] {
+        &self.positions
+    }
+
+    // u across roads, v along them, both a unit per road width
+    pub fn uvs(&self) -> &[[f32; 2]] {
+        &self.uvs
+    }
+
+    // three per triangle
+    pub fn indices(&self) -> &[u32] {
+        &self.indices
+    }
+
+    // positions only, the uvs are already relative to road widths
+    pub fn scale(&mut self, factor: f64) {
+        for position in &mut self.positions {
+            for axis in position.iter_mut() {
+                *axis *= factor as f32;
+            }
+        }
+    }
+
+    fn add_vertex(&mut self, point: &Point, height: f64, uv: [f64; 2]) -> u32 {
+        self.positions.push([point.x() as f32, height as f32, point.y() as f32]);
+        self.uvs.push([uv[0] as f32, uv[1] as f32]);
+        (self.positions.len() - 1) as u32
+    }
+
+    fn add_triangle(&mut self, a: u32, b: u32, c: u32) {
+        let position = |i: u32| self.positions[i as usize];
+        let (pa, pb, pc) = (position(a), position(b), position(c));
+        let cross = (pb[0] - pa[0]) as f64 * (pc[2] - pa[2]) as f64 -
+                    (pb[2] - pa[2]) as f64 * (pc[0] - pa[0]) as f64;
+
+        if cross.abs() < MIN_AREA {
+            return;
+        }
+
+        // anticlockwise from above
+        if cross < 0.0 {
+            self.indices.extend_from_slice(&[a, b, c]);
+        } else {
+            self.indices.extend_from_slice(&[a, c, b]);
+        }
+    }
+}
+
+// a quad for each road, cut back where it meets others so the junction can be filled with
+// a polygon between them
+pub fn build(graph: &RoadGraph, roads: &[Road]) -> Mesh {
+    let widths: Vec<f64> = roads.iter().map(|r| r.width().max(MIN_WIDTH)).collect();
+    let junctions: Vec<(NodeId, Vec<Exit>)> = (0..graph.nodes().len())
+        .map(NodeId)
+        .filter(|&n| graph.node(n).degree() > 1)
+        .map(|n| (n, graph.exits(n)))
+        .collect();
+
+    // how far back from each end each road stops
+    let mut trims = vec![[0.0; 2]; roads.len()];
+    for (node, exits) in &junctions {
+        let n = exits.len();
+        for (i, exit) in exits.iter().enumerate() {
+            let width = widths[exit.edge.0];
+            let trim = [exits[(i + n - 1) % n], exits[(i + 1) % n]]
+                .iter()
+                .map(|other| {
+                    let between = geometry::angle_between(exit.angle, other.angle).max(MIN_ANGLE);
+                    width.max(widths[other.edge.0]) / 2.0 / (between / 2.0).tan()
+                })
+                .fold(0.0, f64::max);
+
+            let end = end_at(graph, exit.edge, *node);
+            trims[exit.edge.0][end] = trim.min(roads[exit.edge.0].length() * MAX_TRIM);
+        }
+    }
+
+    let mut mesh = Mesh::default();
+
+    for (i, road) in roads.iter().enumerate() {
+        let (width, length) = (widths[i], road.length());
+        let [start, end] = trims[i];
+
+        let (near, far) = (corners(road, width, start), corners(road, width, length - end));
+        let height = |distance: f64| road_height(road, distance / length);
+
+        let a = mesh.add_vertex(&near.0, height(start), [0.0, start / width]);
+        let b = mesh.add_vertex(&near.1, height(start), [1.0, start / width]);
+        let c = mesh.add_vertex(&far.0, height(length - end), [0.0, (length - end) / width]);
+        let d = mesh.add_vertex(&far.1, height(length - end), [1.0, (length - end) / width]);
+        mesh.add_triangle(a, b, c);
+        mesh.add_triangle(b, d, c);
+    }
+
+    for (node, exits) in &junctions {
+        let centre = graph.node(*node).position();
+        let scale = exits.iter().map(|e| widths[e.edge.0]).fold(0.0, f64::max);
+        let uv = |p: &Point| [p.x() / scale, p.y() / scale];
+
+        // each road's corners in the same order around the junction as the roads
+        let mut outline = Vec::with_capacity(exits.len() * 2);
+        let mut height = 0.0;
+        for exit in exits {
+            let road = &roads[exit.edge.0];
+            let length = road.length();
+            let end = end_at(graph, exit.edge, *node);
+            let distance = if end == 0 {
+                trims[exit.edge.0][0]
+            } else {
+                length - trims[exit.edge.0][1]
+            };
+
+            height = road_height(road, distance / length);
+            let (left, right) = corners(road, widths[exit.edge.0], distance);
+            let (first, second) = if end == 0 { (left, right) } else { (right, left) };
+
+            outline.push(mesh.add_vertex(&first, height, uv(&first)));
+            outline.push(mesh.add_vertex(&second, height, uv(&second)));
+        }
+
+        let middle = mesh.add_vertex(&centre, height, uv(&centre));
+        for i in 0..outline.len() {
+            mesh.add_triangle(middle, outline[i], outline[(i + 1) % outline.len()]);
+        }
+    }
+
+    mesh
+}
+
+// 0 if the edge starts at the node, 1 if it ends there
+fn end_at(graph: &RoadGraph, edge: EdgeId, node: NodeId) -> usize {
+    if graph.edge(edge).from() == node { 0 } else { 1 }
+}
+
+// either side of the road the given distance along it, to the left then right of
+// the direction it goes in with y down
+fn corners(road: &Road, width: f64, distance: f64) -> (Point, Point) {
+    let (from, to) = (road.from().unwrap(), road.to().unwrap());
+    let length = road.length().max(1e-9);
+    let (dx, dy) = ((to.x() - from.x()) / length, (to.y() - from.y()) / length);
+    let (nx, ny) = (dy * width / 2.0, -dx * width / 2.0);
+
+    let (x, y) = (from.x() + dx * distance, from.y() + dy * distance);
+    (Point::new(x + nx, y + ny), Point::new(x - nx, y - ny))
+}
+
+// flat at 0 without terrain
+fn road_height(road: &Road, along: f64) -> f64 {
+    road.elevation()
+        .map(|(from, to)| from + (to - from) * along)
+        .unwrap_or(0.0)
+}
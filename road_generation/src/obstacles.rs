@@ -0,0 +1,100 @@
+use {Point, RoadError};
+use config::ObstacleConfig;
+use field::{self, ScalarField};
+use geometry;
+
+// distance between mask samples along a segment, in world units
+const MASK_STEP: f64 = 1.0;
+
+// water and other areas roads cannot be built on, other than by bridging
+pub struct ObstacleMap {
+    polygons: Vec<Vec<Point>>,
+    mask: Option<Box<dyn ScalarField>>,
+    mask_threshold: f64,
+}
+
+impl ObstacleMap {
+    pub fn new(polygons: Vec<Vec<Point>>) -> ObstacleMap {
+        ObstacleMap {
+            polygons,
+            mask: None,
+            mask_threshold: 0.0,
+        }
+    }
+
+    pub fn from_config(config: &ObstacleConfig,
+                       seed: u64,
+                       map_width: u32,
+                       map_height: u32)
+                       -> Result<ObstacleMap, RoadError> {
+        let polygons = config
+            .polygons
+            .iter()
+            .map(|poly| poly.iter().map(|&p| Point::from(p)).collect())
+            .collect();
+
+        let mut obstacles = ObstacleMap::new(polygons);
+        if let Some(ref mask) = config.mask {
+            obstacles.mask = Some(field::from_config(mask, seed, map_width, map_height)?);
+            obstacles.mask_threshold = config.mask_threshold;
+        }
+
+        Ok(obstacles)
+    }
+
+    // anything below the threshold is an obstacle
+    pub fn set_mask<F: ScalarField + 'static>(&mut self, mask: F, threshold: f64) {
+        self.mask = Some(Box::new(mask));
+        self.mask_threshold = threshold;
+    }
+
+    pub fn polygons(&self) -> &[Vec<Point>] {
+        &self.polygons
+    }
+
+    pub fn contains(&self, point: &Point) -> bool {
+        let masked = self.mask
+            .as_ref()
+            .is_some_and(|m| m.sample(point.x(), point.y()) < self.mask_threshold);
+
+        masked || self.polygons.iter().any(|poly| geometry::point_in_polygon(point, poly))
+    }
+
+    // ranges of t along a->b that are obstructed, in order
+    pub fn intervals(&self, a: &Point, b: &Point) -> Vec<(f64, f64)> {
+        let mut breaks = vec![0.0, 1.0];
+
+        for poly in &self.polygons {
+            let edges = poly.iter().zip(poly.iter().cycle().skip(1));
+            for (c, d) in edges {
+                if let Some((t, _)) = geometry::segment_intersection(a, b, c, d) {
+                    breaks.push(t);
+                }
+            }
+        }
+
+        if self.mask.is_some() {
+            let steps = (geometry::distance(a, b) / MASK_STEP) as usize;
+            breaks.extend((1..steps + 1).map(|i| i as f64 / (steps + 1) as f64));
+        }
+
+        breaks.sort_by(|x, y| x.partial_cmp(y).unwrap());
+        breaks.dedup();
+
+        let mut intervals: Vec<(f64, f64)> = Vec::new();
+        for pair in breaks.windows(2) {
+            let (start, end) = (pair[0], pair[1]);
+            if !self.contains(&geometry::lerp(a, b, (start + end) / 2.0)) {
+                continue;
+            }
+
+            // extend a contiguous obstruction
+            match intervals.last_mut() {
+                Some(last) if last.1 == start => last.1 = end,
+                _ => intervals.push((start, end)),
+            }
+        }
+
+        intervals
+    }
+}
@@ -1,23 +1,16 @@
-use RoadType;
-use rand::{random, Closed01};
+use rand::{Rng, XorShiftRng, Closed01};
 use std::f64::consts::PI;
-use cgmath::Point2;
-use super::{Proposal, Proposals};
-use config::GenerationConfig;
+use super::{Proposal, Proposals, RuleInput};
 
-pub fn propose(point: &Point2<f64>,
-               cur_angle: f64,
-               road_type: RoadType,
-               branch: bool,
-               config: &GenerationConfig,
-               out: &mut Proposals) {
+pub fn propose(input: &RuleInput, rng: &mut XorShiftRng, out: &mut Proposals) {
     const GRID_ANGLES: [f64; 3] = [-PI / 2., 0., PI / 2.];
+    let RuleInput { point, cur_angle, road_type, branch, config, .. } = *input;
 
     if !branch {
         out.push(Proposal {
                      road_type: road_type,
                      angle: cur_angle + GRID_ANGLES[1], // straight
-                     from: *point,
+                     from: point,
                      length: config.road_length,
                  });
     } else {
@@ -25,7 +18,7 @@ pub fn propose(point: &Point2<f64>,
         for grid_angle in &GRID_ANGLES {
 
             // unlucky
-            let Closed01(chance) = random::<Closed01<f64>>();
+            let Closed01(chance) = rng.gen::<Closed01<f64>>();
             if chance > config.road_chance {
                 continue;
             }
@@ -33,7 +26,7 @@ pub fn propose(point: &Point2<f64>,
             out.push(Proposal {
                          road_type: road_type,
                          angle: cur_angle + grid_angle,
-                         from: *point,
+                         from: point,
                          length: config.road_length,
                      });
         }
@@ -3,14 +3,17 @@ use cgmath::{Point2, Angle, Rad};
 use cgmath::prelude::*;
 use config;
 use smallvec::SmallVec;
-use rand::{thread_rng, Rng};
+use rand::{Rng, XorShiftRng};
 
 mod grid;
 mod organic;
+mod radial;
 
-enum GenerationRule {
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GenerationRule {
     Grid,
-    // Radial,
+    Radial,
     Organic,
 }
 
@@ -22,12 +25,19 @@ macro_rules! new_proposals {
   }
 }
 
-type ProposalGenerator = fn(&Point2<f64>,
-                            f64,
-                            RoadType,
-                            bool,
-                            &config::GenerationConfig,
-                            &mut Proposals);
+type ProposalGenerator = fn(&RuleInput, &mut XorShiftRng, &mut Proposals);
+
+// everything a rule needs to know about the road being extended
+pub struct RuleInput<'a> {
+    pub point: Point2<f64>,
+    pub cur_angle: f64,
+    pub road_type: RoadType,
+    pub branch: bool,
+    pub config: &'a config::GenerationConfig,
+
+    // for rules that grow around a point
+    pub centre: Point2<f64>,
+}
 
 #[derive(Copy, Clone)]
 pub struct Proposal {
@@ -53,9 +63,10 @@ impl Proposal {
     }
 }
 
-pub fn propose_roads(config: &config::GenerationConfig,
+pub fn propose_roads(root_config: &config::Config,
                      road: &Road,
                      branch: bool,
+                     rng: &mut XorShiftRng,
                      out: &mut Vec<Road>) {
 
     let from = Point2::from(road.from().unwrap().pos);
@@ -66,18 +77,26 @@ pub fn propose_roads(config: &config::GenerationConfig,
 
     let mut proposals: Proposals = new_proposals!();
 
-    let rule = get_rule(road.to().as_ref().unwrap());
+    let point = road.to().unwrap();
+    let config = root_config.generation_at(&road.road_type(), &point);
+    let zone = root_config.zone_at(&point);
+    let centre = zone.and_then(|z| z.centre()).unwrap_or_else(|| root_config.window_centre());
+
+    let input = RuleInput {
+        point: to,
+        cur_angle,
+        road_type: road.road_type(),
+        branch,
+        config,
+        centre: Point2::from(centre),
+    };
+
+    let rule = zone.map_or(GenerationRule::Grid, |z| z.rule);
     if let Some(generator) = get_generator(&rule) {
-        (generator)(&to,
-                    cur_angle,
-                    road.road_type(),
-                    branch,
-                    config,
-                    &mut proposals);
+        (generator)(&input, rng, &mut proposals);
     }
 
-    let mut rng = thread_rng();
-    let child_type = get_next_type(road.road_type);
+    let child_type = root_config.child(road.road_type);
 
     for p in &proposals {
         out.push(p.to_road());
@@ -94,27 +113,10 @@ pub fn propose_roads(config: &config::GenerationConfig,
     }
 }
 
-fn get_rule(point: &Point) -> GenerationRule {
-    if point.x() < 400.0 || point.x() > 600.0 {
-        // arbitrary nonsense
-        GenerationRule::Grid
-    } else {
-        GenerationRule::Organic
-    }
-}
-
 fn get_generator(rule: &GenerationRule) -> Option<ProposalGenerator> {
     match *rule {
         GenerationRule::Grid => Some(grid::propose),
+        GenerationRule::Radial => Some(radial::propose),
         GenerationRule::Organic => Some(organic::propose),
-        // _ => None,
-    }
-}
-
-fn get_next_type(road_type: RoadType) -> Option<RoadType> {
-    match road_type {
-        RoadType::Large => Some(RoadType::Medium),
-        RoadType::Medium => Some(RoadType::Small),
-        _ => None,
     }
 }
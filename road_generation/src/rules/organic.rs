@@ -1,21 +1,12 @@
-use RoadType;
-use rand::{thread_rng, Rng};
-use cgmath::Point2;
-use super::{Proposals, grid};
-use config::GenerationConfig;
+use rand::{Rng, XorShiftRng};
+use super::{Proposals, RuleInput, grid};
 
-pub fn propose(point: &Point2<f64>,
-               cur_angle: f64,
-               road_type: RoadType,
-               branch: bool,
-               config: &GenerationConfig,
-               out: &mut Proposals) {
-    grid::propose(point, cur_angle, road_type, branch, config, out);
+pub fn propose(input: &RuleInput, rng: &mut XorShiftRng, out: &mut Proposals) {
+    grid::propose(input, rng, out);
 
 
     // vary grid angle
-    let mut rng = thread_rng();
-    let variation = config.organic_angle.to_radians();
+    let variation = input.config.organic_angle.to_radians();
 
     for prop in out.iter_mut() {
         let variation = rng.gen_range(-variation, variation);
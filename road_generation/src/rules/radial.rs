@@ -0,0 +1,55 @@
+use rand::{Rng, XorShiftRng, Closed01};
+use std::f64::consts::{PI, FRAC_PI_2};
+use super::{Proposal, Proposals, RuleInput, grid};
+use geometry::angle_between;
+
+// spokes out from the centre and rings around it
+pub fn propose(input: &RuleInput, rng: &mut XorShiftRng, out: &mut Proposals) {
+    let offset = input.point - input.centre;
+    let radius = offset.x.hypot(offset.y);
+
+    // no sensible spoke direction this close
+    if radius < input.config.road_length {
+        return grid::propose(input, rng, out);
+    }
+
+    let spoke = offset.y.atan2(offset.x);
+
+    // turn rings inwards so each segment is a chord of the circle
+    let chord = (input.config.road_length / (2.0 * radius)).min(1.0).asin();
+
+    // outwards, anticlockwise, inwards, clockwise
+    let headings = [spoke, spoke + FRAC_PI_2 + chord, spoke + PI, spoke - FRAC_PI_2 - chord];
+
+    // continue along whichever is closest to the current direction
+    let straight = (0..headings.len())
+        .min_by(|&a, &b| {
+                    let a = angle_between(headings[a], input.cur_angle);
+                    let b = angle_between(headings[b], input.cur_angle);
+                    a.partial_cmp(&b).unwrap()
+                })
+        .unwrap();
+
+    let mut push = |heading: f64| {
+        out.push(Proposal {
+                     road_type: input.road_type,
+                     angle: heading,
+                     from: input.point,
+                     length: input.config.road_length,
+                 })
+    };
+
+    if !input.branch {
+        push(headings[straight]);
+    } else {
+        // right, straight on and left, as with the grid
+        for turn in &[3, 0, 1] {
+            let Closed01(chance) = rng.gen::<Closed01<f64>>();
+            if chance > input.config.road_chance {
+                continue;
+            }
+
+            push(headings[(straight + turn) % headings.len()]);
+        }
+    }
+}
@@ -0,0 +1,333 @@
+use {Point, Road, RoadProfile, Surface, RoadMap, RoadError};
+use config::Config;
+use serde_json;
+use bincode;
+use std::fmt::Display;
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::BufWriter;
+use std::path::Path;
+
+// bumped whenever the format changes, maps of any other version are rejected
+pub const VERSION: u32 = 1;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MapFormat {
+    // readable without the generator
+    Json,
+    // smaller and quicker to read
+    Binary,
+}
+
+impl MapFormat {
+    // binary for .bin, json for anything else
+    pub fn from_path<P: AsRef<Path>>(path: P) -> MapFormat {
+        match path.as_ref().extension().and_then(|e| e.to_str()) {
+            Some("bin") => MapFormat::Binary,
+            _ => MapFormat::Json,
+        }
+    }
+}
+
+// the start of every map, read before the rest
+#[derive(Deserialize)]
+struct Header {
+    version: u32,
+}
+
+// the config has internally tagged enums that bincode can't read back, so binary maps
+// keep it as json
+#[derive(Serialize, Deserialize)]
+struct SavedMap<C> {
+    version: u32,
+    seed: u64,
+    config: C,
+    centres: Vec<[f64; 2]>,
+    roads: Vec<SavedRoad>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedRoad {
+    from: [f64; 2],
+    to: [f64; 2],
+    // name of the road class in the config
+    class: String,
+    fuel: u32,
+    bridge: bool,
+    // at from and to
+    elevation: Option<[f64; 2]>,
+    planned: bool,
+    street: usize,
+    width: f64,
+    lanes: u32,
+    speed_limit: f64,
+    surface: Surface,
+}
+
+impl RoadMap {
+    // the roads along with the config and seed they were grown from
+    pub fn save<P: AsRef<Path>>(&self, path: P, format: MapFormat) -> Result<(), RoadError> {
+        let path = path.as_ref();
+        if !self.is_finished() {
+            return Err(RoadError::Map(String::from("Only finished maps can be saved")));
+        }
+
+        let failed = |e: &dyn Display| {
+            RoadError::Map(format!("Failed to write map '{}': {}", path.display(), e))
+        };
+
+        let file = File::create(path).map_err(|e| failed(&e))?;
+        let mut writer = BufWriter::new(file);
+
+        match format {
+            MapFormat::Json => {
+                serde_json::to_writer_pretty(&mut writer, &self.saved(&self.config))
+                    .map_err(|e| failed(&e))?
+            }
+            MapFormat::Binary => {
+                let config = serde_json::to_string(&self.config).map_err(|e| failed(&e))?;
+                bincode::serialize_into(&mut writer, &self.saved(config)).map_err(|e| failed(&e))?
+            }
+        }
+
+        writer.flush().map_err(|e| failed(&e))
+    }
+
+    // a finished map saved in the given format, which can't grow any further
+    pub fn load<P: AsRef<Path>>(path: P, format: MapFormat) -> Result<RoadMap, RoadError> {
+        let path = path.as_ref();
+        let invalid = |e: &dyn Display| {
+            RoadError::Map(format!("Invalid map '{}': {}", path.display(), e))
+        };
+
+        let mut bytes = Vec::new();
+        File::open(path)
+            .and_then(|mut f| f.read_to_end(&mut bytes))
+            .map_err(|e| RoadError::Map(format!("Failed to read map '{}': {}", path.display(), e)))?;
+
+        let header: Header = match format {
+            MapFormat::Json => serde_json::from_slice(&bytes).map_err(|e| invalid(&e))?,
+            MapFormat::Binary => bincode::deserialize(&bytes).map_err(|e| invalid(&e))?,
+        };
+
+        if header.version != VERSION {
+            return Err(RoadError::Map(format!("Map '{}' is version {}, expected {}",
+                                              path.display(),
+                                              header.version,
+                                              VERSION)));
+        }
+
+        let saved = match format {
+            MapFormat::Json => {
+                serde_json::from_slice::<SavedMap<Config>>(&bytes).map_err(|e| invalid(&e))?
+            }
+            MapFormat::Binary => {
+                let saved: SavedMap<String> = bincode::deserialize(&bytes).map_err(|e| invalid(&e))?;
+                let config = serde_json::from_str(&saved.config).map_err(|e| invalid(&e))?;
+                saved.with_config(config)
+            }
+        };
+
+        saved.into_roadmap()
+    }
+
+    fn saved<C>(&self, config: C) -> SavedMap<C> {
+        let roads = self.roads
+            .iter()
+            .map(|road| {
+                SavedRoad {
+                    from: road.from.unwrap().pos,
+                    to: road.to.unwrap().pos,
+                    class: self.config.class(road.road_type).name.clone(),
+                    fuel: road.fuel,
+                    bridge: road.bridge,
+                    elevation: road.elevation.map(|(from, to)| [from, to]),
+                    planned: road.planned,
+                    street: road.street,
+                    width: road.profile.width,
+                    lanes: road.profile.lanes,
+                    speed_limit: road.profile.speed_limit,
+                    surface: road.profile.surface,
+                }
+            })
+            .collect();
+
+        SavedMap {
+            version: VERSION,
+            seed: self.seed,
+            config,
+            centres: self.centres.iter().map(|c| c.pos).collect(),
+            roads,
+        }
+    }
+}
+
+impl<C> SavedMap<C> {
+    fn with_config<D>(self, config: D) -> SavedMap<D> {
+        SavedMap {
+            version: self.version,
+            seed: self.seed,
+            config,
+            centres: self.centres,
+            roads: self.roads,
+        }
+    }
+}
+
+impl SavedMap<Config> {
+    fn into_roadmap(self) -> Result<RoadMap, RoadError> {
+        let mut config = self.config;
+        config.resolve_classes().map_err(RoadError::Settings)?;
+
+        let mut roads = Vec::with_capacity(self.roads.len());
+        for road in self.roads {
+            let road_type = config
+                .road_type(&road.class)
+                .ok_or_else(|| RoadError::Map(format!("Unknown road class '{}'", road.class)))?;
+
+            roads.push(Road {
+                           from: Some(Point::from(road.from)),
+                           to: Some(Point::from(road.to)),
+                           road_type,
+                           fuel: road.fuel,
+                           bridge: road.bridge,
+                           elevation: road.elevation.map(|e| (e[0], e[1])),
+                           planned: road.planned,
+                           street: road.street,
+                           profile: RoadProfile {
+                               width: road.width,
+                               lanes: road.lanes,
+                               speed_limit: road.speed_limit,
+                               surface: road.surface,
+                           },
+                       });
+        }
+
+        let centres = self.centres.into_iter().map(Point::from).collect();
+        RoadMap::with_roads(config, self.seed, roads, centres)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use toml;
+    use std::{env, fs, process};
+
+    const CONFIG: &str = r#"
+        [window]
+        width = 200
+        height = 200
+
+        [[classes]]
+        name = "large"
+        child = "small"
+
+        [classes.generation]
+        fuel_range = [2, 4]
+        road_chance = 0.8
+        road_length = 20.0
+        merge_range = 18.0
+        organic_angle = 15
+        child_chance = 0.2
+
+        [[classes]]
+        name = "small"
+
+        [classes.generation]
+        fuel_range = [1, 2]
+        road_chance = 0.5
+        road_length = 8.0
+        merge_range = 6.0
+        organic_angle = 15
+        child_chance = 0.3
+        surface = "cobbles"
+
+        [[zones]]
+        rule = "organic"
+
+        [zones.generation.small]
+        road_length = 6.0
+
+        [cleanup]
+        min_dead_end = 2
+        extend_range = 5.0
+        min_component = 5
+
+        [terrain]
+        height_scale = 20.0
+
+        [terrain.field]
+        source = "noise"
+        scale = 100.0
+        octaves = 2
+    "#;
+
+    fn roadmap() -> RoadMap {
+        let mut config: Config = toml::from_str(CONFIG).unwrap();
+        config.resolve_classes().unwrap();
+
+        let mut roadmap = RoadMap::with_seed(config, 7).unwrap();
+        while !roadmap.is_finished() {
+            roadmap.advance().unwrap();
+        }
+        roadmap
+    }
+
+    fn round_trip(roadmap: &RoadMap, name: &str, format: MapFormat) -> RoadMap {
+        let path = env::temp_dir().join(format!("road_generation_{}_{}", process::id(), name));
+        roadmap.save(&path, format).unwrap();
+        let loaded = RoadMap::load(&path, format);
+        fs::remove_file(&path).unwrap();
+        loaded.unwrap()
+    }
+
+    fn assert_same(a: &RoadMap, b: &RoadMap) {
+        assert_eq!(a.seed(), b.seed());
+        assert_eq!(a.centres(), b.centres());
+        assert_eq!(a.graph().nodes().len(), b.graph().nodes().len());
+        assert_eq!(a.roads().len(), b.roads().len());
+
+        for (x, y) in a.roads().iter().zip(b.roads()) {
+            assert_eq!(x.points(), y.points());
+            assert_eq!(x.road_type(), y.road_type());
+            assert_eq!(x.fuel(), y.fuel());
+            assert_eq!(x.is_bridge(), y.is_bridge());
+            assert_eq!(x.elevation(), y.elevation());
+            assert_eq!(x.is_planned(), y.is_planned());
+            assert_eq!(x.street(), y.street());
+            assert_eq!(x.profile(), y.profile());
+        }
+
+        // the zone override is merged onto the class again when loaded
+        let small = b.config().road_type("small").unwrap();
+        let generation = b.config().generation_at(&small, &Point::new(50.0, 50.0));
+        assert_eq!(generation.road_length, 6.0);
+        assert_eq!(generation.surface, Surface::Cobbles);
+    }
+
+    // nothing left to grow or clean up
+    fn assert_finished(mut loaded: RoadMap) {
+        let roads = loaded.roads().len();
+        loaded.advance().unwrap();
+        assert_eq!(loaded.roads().len(), roads);
+        assert_eq!(loaded.cleanups(), 0);
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let roadmap = roadmap();
+        assert!(!roadmap.roads().is_empty());
+        let loaded = round_trip(&roadmap, "map.json", MapFormat::Json);
+        assert_same(&roadmap, &loaded);
+        assert_finished(loaded);
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        let roadmap = roadmap();
+        let loaded = round_trip(&roadmap, "map.bin", MapFormat::Binary);
+        assert_same(&roadmap, &loaded);
+        assert_finished(loaded);
+    }
+}
@@ -0,0 +1,187 @@
+use {Point, Road, RoadType, EdgeId};
+use geometry;
+use graph;
+use std::collections::HashMap;
+
+// curve points between each pair of street points, before resampling
+const SUBDIVISIONS: usize = 8;
+
+// roads grown one after another in the same direction, drawn as a single curve
+#[derive(Debug, Clone)]
+pub struct Street {
+    id: usize,
+    road_type: RoadType,
+    points: Vec<Point>,
+    roads: Vec<EdgeId>,
+}
+
+impl Street {
+    // shared by every piece of a street broken up by cleanup
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    pub fn road_type(&self) -> RoadType {
+        self.road_type
+    }
+
+    // the ends of the roads in order, first and last are the same for loops
+    pub fn points(&self) -> &[Point] {
+        &self.points
+    }
+
+    // in order along the street
+    pub fn roads(&self) -> &[EdgeId] {
+        &self.roads
+    }
+
+    pub fn is_loop(&self) -> bool {
+        self.points.len() > 3 && self.points.first() == self.points.last()
+    }
+
+    pub fn length(&self) -> f64 {
+        self.points
+            .windows(2)
+            .map(|pair| geometry::distance(&pair[0], &pair[1]))
+            .sum()
+    }
+
+    // points about spacing apart along a smooth curve through the street's points,
+    // including both ends
+    pub fn sample(&self, spacing: f64) -> Vec<Point> {
+        let curve = self.curve();
+        if curve.len() < 2 || spacing <= 0.0 {
+            return curve;
+        }
+
+        let mut samples = vec![curve[0]];
+        let mut travelled = 0.0;
+        let mut next = spacing;
+
+        for pair in curve.windows(2) {
+            let length = geometry::distance(&pair[0], &pair[1]);
+            while length > 0.0 && travelled + length >= next {
+                samples.push(geometry::lerp(&pair[0], &pair[1], (next - travelled) / length));
+                next += spacing;
+            }
+            travelled += length;
+        }
+
+        // the end exactly, replacing a sample too close to it
+        let end = *curve.last().unwrap();
+        if samples.len() > 1 && geometry::distance(samples.last().unwrap(), &end) < spacing / 2.0 {
+            samples.pop();
+        }
+        samples.push(end);
+        samples
+    }
+
+    // catmull-rom through the points
+    fn curve(&self) -> Vec<Point> {
+        let points = &self.points;
+        let n = points.len();
+        if n < 3 {
+            return points.clone();
+        }
+
+        // loops wrap around past the repeated point, open streets repeat their ends
+        let looped = self.is_loop();
+        let control = |i: isize| -> Point {
+            if looped {
+                points[(i + n as isize - 1) as usize % (n - 1)]
+            } else {
+                points[i.clamp(0, n as isize - 1) as usize]
+            }
+        };
+
+        let mut curve = Vec::with_capacity((n - 1) * SUBDIVISIONS + 1);
+        for i in 0..(n - 1) as isize {
+            let (p0, p1, p2, p3) = (control(i - 1), control(i), control(i + 1), control(i + 2));
+            for step in 0..SUBDIVISIONS {
+                curve.push(catmull_rom(&p0, &p1, &p2, &p3, step as f64 / SUBDIVISIONS as f64));
+            }
+        }
+        curve.push(points[n - 1]);
+        curve
+    }
+}
+
+// between p1 and p2 at t
+fn catmull_rom(p0: &Point, p1: &Point, p2: &Point, p3: &Point, t: f64) -> Point {
+    let (t2, t3) = (t * t, t * t * t);
+    let axis = |a: f64, b: f64, c: f64, d: f64| {
+        0.5 *
+        (2.0 * b + (c - a) * t + (2.0 * a - 5.0 * b + 4.0 * c - d) * t2 +
+         (3.0 * b - a - 3.0 * c + d) * t3)
+    };
+
+    Point::new(axis(p0.x(), p1.x(), p2.x(), p3.x()),
+               axis(p0.y(), p1.y(), p2.y(), p3.y()))
+}
+
+// chains roads with the same street into streets, in order of each street's first road
+pub fn trace(roads: &[Road]) -> Vec<Street> {
+    let mut order: Vec<usize> = Vec::new();
+    let mut groups: HashMap<usize, Vec<usize>> = HashMap::new();
+    for (i, road) in roads.iter().enumerate() {
+        groups
+            .entry(road.street())
+            .or_insert_with(|| {
+                                order.push(road.street());
+                                Vec::new()
+                            })
+            .push(i);
+    }
+
+    let mut streets = Vec::new();
+    for id in order {
+        trace_street(roads, id, &groups[&id], &mut streets);
+    }
+    streets
+}
+
+// roads grow from the end of the last, so follow each to to the next from.
+// cleanup may have broken the street into several pieces
+fn trace_street(roads: &[Road], id: usize, group: &[usize], out: &mut Vec<Street>) {
+    let mut starting: HashMap<[u64; 2], Vec<usize>> = HashMap::new();
+    for &i in group {
+        starting.entry(graph::key(&roads[i].from().unwrap())).or_default().push(i);
+    }
+
+    let ends: Vec<[u64; 2]> = group.iter().map(|&i| graph::key(&roads[i].to().unwrap())).collect();
+    let mut used: Vec<usize> = Vec::with_capacity(group.len());
+
+    while used.len() < group.len() {
+        // prefer a road nothing leads into, otherwise this piece is a loop
+        let start = group
+            .iter()
+            .cloned()
+            .filter(|i| !used.contains(i))
+            .find(|&i| !ends.contains(&graph::key(&roads[i].from().unwrap())))
+            .or_else(|| group.iter().cloned().find(|i| !used.contains(i)))
+            .unwrap();
+
+        let mut points = vec![roads[start].from().unwrap()];
+        let mut edges = Vec::new();
+        let mut current = Some(start);
+
+        while let Some(i) = current {
+            used.push(i);
+            edges.push(EdgeId(i));
+
+            let to = roads[i].to().unwrap();
+            points.push(to);
+
+            current = starting
+                .get(&graph::key(&to))
+                .and_then(|next| next.iter().cloned().find(|n| !used.contains(n)));
+        }
+
+        out.push(Street {
+                     id,
+                     road_type: roads[start].road_type(),
+                     points,
+                     roads: edges,
+                 });
+    }
+}
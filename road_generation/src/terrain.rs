@@ -0,0 +1,59 @@
+use {Point, RoadError};
+use config::TerrainConfig;
+use field::{self, ScalarField};
+use geometry;
+
+// distance between heights compared along a segment, close enough to catch a ridge
+// between its ends
+const SAMPLE_SPACING: f64 = 2.0;
+
+// elevation at every point of the map
+pub struct Heightmap {
+    field: Box<dyn ScalarField>,
+
+    // elevation of a field value of 1
+    height_scale: f64,
+}
+
+impl Heightmap {
+    pub fn new<F: ScalarField + 'static>(field: F, height_scale: f64) -> Heightmap {
+        Heightmap {
+            field: Box::new(field),
+            height_scale,
+        }
+    }
+
+    pub fn from_config(config: &TerrainConfig,
+                       seed: u64,
+                       map_width: u32,
+                       map_height: u32)
+                       -> Result<Heightmap, RoadError> {
+        Ok(Heightmap {
+               field: field::from_config(&config.field, seed, map_width, map_height)?,
+               height_scale: config.height_scale,
+           })
+    }
+
+    pub fn height(&self, point: &Point) -> f64 {
+        self.field.sample(point.x(), point.y()) * self.height_scale
+    }
+
+    // steepest absolute rise over run anywhere between two points
+    pub fn slope(&self, a: &Point, b: &Point) -> f64 {
+        let run = geometry::distance(a, b);
+        if run <= 0.0 {
+            return 0.0;
+        }
+
+        let steps = (run / SAMPLE_SPACING).ceil();
+        let mut previous = self.height(a);
+        let mut steepest: f64 = 0.0;
+        for i in 1..steps as usize + 1 {
+            let height = self.height(&geometry::lerp(a, b, i as f64 / steps));
+            steepest = steepest.max((height - previous).abs());
+            previous = height;
+        }
+
+        steepest / (run / steps)
+    }
+}
@@ -1,14 +1,13 @@
 extern crate road_generation;
 extern crate sfml;
 
-#[macro_use]
-extern crate lazy_static;
-
 use std::process;
 use std::env;
 use std::fs;
 use std::path;
-use road_generation::{RoadError, RoadMap, Config};
+use road_generation::{RoadError, RoadMap, Config, Point};
+use road_generation::save::MapFormat;
+use road_generation::export::ExportFormat;
 
 use sfml::system::*;
 use sfml::window::{ContextSettings, VideoMode, Event, style, Key};
@@ -16,33 +15,42 @@ use sfml::graphics::*;
 
 const CONFIG_PATH: &'static str = "config.toml";
 
-lazy_static! {
-static ref ROAD_RENDERING: [(Color, u32); 3] = [
-(Color::rgb(0, 0, 255), 1), // small
-(Color::rgb(0, 0, 0), 3), // medium
-(Color::rgb(255, 0, 0), 5), // large
-];
-}
+// written by generate if no path is given, binary for .bin and json otherwise
+const MAP_PATH: &'static str = "roadmap.json";
+
+// distance between points drawn along streets
+const STREET_SPACING: f64 = 2.0;
 
 enum Action {
-    GenerateOnly,
+    GenerateOnly(String),
+    Export(String, ExportFormat),
     Window,
     Image,
 }
 
 fn parse_args() -> Result<Action, RoadError> {
-    let mut args = env::args();
+    let args: Vec<String> = env::args().collect();
 
-    if args.nth(2).is_some() {
-        println!("Expected optional single argument of 'generate' or 'image'");
+    if args.len() > 3 || (args.len() == 3 && args[1] != "generate" && args[1] != "export") {
+        println!("Expected optional argument of 'generate [path]', 'export <path>' or 'image'");
         return Err(RoadError::Args(String::from("Too many args")));
     }
 
-    match env::args().nth(1) {
+    match args.get(1) {
         None => Ok(Action::Window),
         Some(what) => {
-            match &*what {
-                "generate" => Ok(Action::GenerateOnly),
+            match &**what {
+                "generate" => {
+                    let path = args.get(2).map(|p| p.as_str()).unwrap_or(MAP_PATH);
+                    Ok(Action::GenerateOnly(String::from(path)))
+                }
+                "export" => {
+                    let path = args.get(2)
+                        .ok_or_else(|| RoadError::Args(String::from("Missing export path")))?;
+                    let format = ExportFormat::from_path(path)
+                        .ok_or_else(|| RoadError::Args(format!("Unknown export format {}", path)))?;
+                    Ok(Action::Export(path.clone(), format))
+                }
                 "image" => Ok(Action::Image),
                 uhoh => Err(RoadError::Args(format!("Unknown argument {}", uhoh))),
             }
@@ -93,8 +101,16 @@ fn update_config(previous: Option<Config>) -> (bool, Config) {
 fn run() -> Result<(), RoadError> {
 
     match parse_args()? {
-        Action::GenerateOnly => {
-            let _roadmap = create_generated()?;
+        Action::GenerateOnly(path) => {
+            let roadmap = create_generated()?;
+            roadmap.save(&path, MapFormat::from_path(&path))?;
+            println!("Saved {} roads to {}", roadmap.roads().len(), path);
+            Ok(())
+        }
+        Action::Export(path, format) => {
+            let roadmap = create_generated()?;
+            roadmap.export(&path, format)?;
+            println!("Exported {} roads to {}", roadmap.roads().len(), path);
             Ok(())
         }
         Action::Image => render_to_image(),
@@ -119,6 +135,7 @@ fn open_window() -> Result<(), RoadError> {
 
     let mut running = true;
     let mut last_count = 0;
+    let mut last_finished = false;
     while running {
         let mut dirty = false;
         for event in window.events() {
@@ -140,10 +157,12 @@ fn open_window() -> Result<(), RoadError> {
 
         // render only if dirty
         let len = roadmap.roads().len();
-        if len != last_count {
+        let finished = roadmap.is_finished();
+        if len != last_count || finished != last_finished {
             dirty = true;
         }
         last_count = len;
+        last_finished = finished;
 
 
         if dirty {
@@ -179,11 +198,36 @@ fn render_roadmap(target: &mut RenderTarget, roadmap: &RoadMap) {
 
     target.draw_primitives(&background, PrimitiveType::Quads, RenderStates::default());
 
+    if let Some(obstacles) = roadmap.obstacles() {
+        let water_colour = Color::rgb(100, 150, 230);
+        for polygon in obstacles.polygons() {
+            let outline: Vec<Vertex> = polygon
+                .iter()
+                .chain(polygon.first())
+                .map(|p| Vertex::with_pos_color(vec(p.x(), p.y()), water_colour))
+                .collect();
+            target.draw_primitives(&outline, PrimitiveType::LineStrip, RenderStates::default());
+        }
+    }
 
     let mut circle = CircleShape::new_init(2.0, 20);
     let rad = circle.radius() as f64;
     circle.set_fill_color(&vertex_colour);
 
+    // too slow to recalculate while growing
+    if roadmap.is_finished() {
+        let building_colour = Color::rgb(150, 150, 150);
+        for building in roadmap.buildings() {
+            let footprint = building.footprint();
+            let outline: Vec<Vertex> = footprint
+                .iter()
+                .chain(footprint.first())
+                .map(|p| Vertex::with_pos_color(vec(p.x(), p.y()), building_colour))
+                .collect();
+            target.draw_primitives(&outline, PrimitiveType::LineStrip, RenderStates::default());
+        }
+    }
+
     let roads = roadmap.roads();
     for road in roads.iter() {
 
@@ -194,16 +238,46 @@ fn render_roadmap(target: &mut RenderTarget, roadmap: &RoadMap) {
                 // target.draw(&circle);
             }
 
-            let (colour, _thickness) = ROAD_RENDERING[road.road_type() as usize];
-
-            let line = [Vertex::with_pos_color(vec(from.x(), from.y()), colour),
-                        Vertex::with_pos_color(vec(to.x(), to.y()), colour)];
+        }
+    }
 
-            target.draw_primitives(&line, PrimitiveType::Lines, RenderStates::default());
+    // as smooth curves through the roads, as wide as the first road of each
+    for street in roadmap.streets() {
+        let [r, g, b] = roadmap.config().class(street.road_type()).colour;
+        let colour = Color::rgb(r, g, b);
+        let width = roadmap.road(street.roads()[0]).width();
+        let points = street.sample(STREET_SPACING);
+
+        if width <= 1.0 {
+            let line: Vec<Vertex> = points
+                .iter()
+                .map(|p| Vertex::with_pos_color(vec(p.x(), p.y()), colour))
+                .collect();
+            target.draw_primitives(&line, PrimitiveType::LineStrip, RenderStates::default());
+        } else {
+            let strip = thick_line(&points, width, colour);
+            target.draw_primitives(&strip, PrimitiveType::TriangleStrip, RenderStates::default());
         }
     }
 }
 
+// a triangle strip either side of the line
+fn thick_line(points: &[Point], width: f64, colour: Color) -> Vec<Vertex> {
+    let mut strip = Vec::with_capacity(points.len() * 2);
+    for (i, p) in points.iter().enumerate() {
+        // along the line, averaged at each bend
+        let before = &points[i.saturating_sub(1)];
+        let after = &points[(i + 1).min(points.len() - 1)];
+        let (dx, dy) = (after.x() - before.x(), after.y() - before.y());
+        let length = dx.hypot(dy).max(1e-9);
+
+        let (nx, ny) = (-dy / length * width / 2.0, dx / length * width / 2.0);
+        strip.push(Vertex::with_pos_color(vec(p.x() + nx, p.y() + ny), colour));
+        strip.push(Vertex::with_pos_color(vec(p.x() - nx, p.y() - ny), colour));
+    }
+    strip
+}
+
 const RENDER_DIR: &'static str = "/tmp/roads";
 const RENDER_COUNT: u32 = 10;
 